@@ -0,0 +1,106 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::isw_raw_access::IswRegisterWidth;
+use crate::isw_state::state_dir;
+use crate::isw_error::IswError;
+
+const BACKUP_PREFIX: &str = "backup-";
+const BACKUP_SUFFIX: &str = ".json";
+
+/// Value of one EC register at the time of the backup
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BackupRegister {
+    pub name: String,
+    pub address: u64,
+    pub width: IswRegisterWidth,
+    pub value: u16,
+}
+
+/// Every writable register of an address profile, as stored in a state file
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IswBackup {
+    /// UTC time of the backup, e.g. "20211024T183005Z"
+    pub created: String,
+    pub address_profile: String,
+    pub registers: Vec<BackupRegister>,
+}
+
+impl IswBackup {
+    pub fn load(file: &str) -> Result<IswBackup, IswError> {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => {
+                return Err(IswError::from_io("Reading file <".to_string() + file + "> failed with <"
+                    + error.to_string().as_str() + ">", &error, IswError::File));
+            }
+        };
+        match serde_json::from_str(text.as_str()) {
+            Ok(backup) => Ok(backup),
+            Err(error) => Err(IswError::Parse("Parsing backup <".to_string() + file + "> failed with <"
+                + error.to_string().as_str() + ">")),
+        }
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), IswError> {
+        if let Some(dir) = Path::new(file).parent() {
+            if let Err(error) = std::fs::create_dir_all(dir) {
+                return Err(IswError::from_io("Creating directory of file <".to_string() + file + "> failed with <"
+                    + error.to_string().as_str() + ">", &error, IswError::File));
+            }
+        }
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(error) => {
+                return Err(IswError::File("Serializing backup failed with <".to_string() + error.to_string().as_str() + ">"));
+            }
+        };
+        match std::fs::write(file, text + "\n") {
+            Ok(()) => Ok(()),
+            Err(error) => Err(IswError::from_io("Writing file <".to_string() + file + "> failed with <"
+                + error.to_string().as_str() + ">", &error, IswError::File)),
+        }
+    }
+
+    /// State file for a backup taken at `created`, below $XDG_STATE_HOME/isw-rs; backups taken
+    /// within the same second get a counter, e.g. "backup-20211024T183005Z-1.json"
+    pub fn default_file(created: &str) -> Result<String, IswError> {
+        let dir = state_dir()?;
+        let mut stem = BACKUP_PREFIX.to_string() + created;
+        let mut counter = 0;
+        while dir.join(stem.clone() + BACKUP_SUFFIX).exists() {
+            counter += 1;
+            stem = BACKUP_PREFIX.to_string() + created + "-" + counter.to_string().as_str();
+        }
+        Ok(dir.join(stem + BACKUP_SUFFIX).to_string_lossy().to_string())
+    }
+
+    /// Timestamp and counter of a state file name, ordering backups by the time they were taken
+    fn sort_key(name: &str) -> Option<(String, u32)> {
+        let stem = name.strip_prefix(BACKUP_PREFIX)?.strip_suffix(BACKUP_SUFFIX)?;
+        match stem.split_once('-') {
+            Some((created, counter)) => Some((created.to_string(), counter.parse().ok()?)),
+            None => Some((stem.to_string(), 0)),
+        }
+    }
+
+    /// Most recent state file below $XDG_STATE_HOME/isw-rs
+    pub fn latest_file() -> Result<String, IswError> {
+        let dir = state_dir()?;
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                return Err(IswError::from_io("Listing backups in <".to_string() + dir.to_string_lossy().as_ref()
+                    + "> failed with <" + error.to_string().as_str() + ">", &error, IswError::File));
+            }
+        };
+        let latest = entries.filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter_map(|name| IswBackup::sort_key(name.as_str()).map(|key| (key, name)))
+            .max()
+            .map(|(_, name)| name);
+        match latest {
+            Some(name) => Ok(dir.join(name).to_string_lossy().to_string()),
+            None => Err(IswError::File("No backup found in <".to_string() + dir.to_string_lossy().as_ref() + ">")),
+        }
+    }
+}
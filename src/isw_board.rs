@@ -0,0 +1,130 @@
+use std::path::Path;
+use crate::isw_error::IswError;
+
+const BOARD_NAME_FILE: &str = "class/dmi/id/board_name";
+const MSI_BOARD_PREFIX: &str = "MS-";
+const BOARD_ID_LEN: usize = 4;
+
+/// Read the DMI board name (e.g. "MS-16J9") below `sysfs_root`
+pub fn read_board_name(sysfs_root: String) -> Result<String, IswError> {
+    let file = Path::new(sysfs_root.as_str()).join(BOARD_NAME_FILE);
+    match std::fs::read_to_string(&file) {
+        Ok(name) => Ok(name.trim().to_string()),
+        Err(error) => Err(IswError::from_io("Reading board name from <".to_string() + file.to_string_lossy().as_ref()
+            + "> failed with <" + error.to_string().as_str() + ">", &error, IswError::UnsupportedBoard))
+    }
+}
+
+/// Strip the vendor prefix of `board_name`, "MS-16J9" becomes "16J9"
+fn board_id(board_name: &str) -> String {
+    let upper = board_name.trim().to_uppercase();
+    upper.trim_start_matches(MSI_BOARD_PREFIX).to_string()
+}
+
+fn count_differences(a: &str, b: &str) -> usize {
+    let same = a.chars().zip(b.chars()).filter(|(x, y)| x == y).count();
+    a.chars().count().max(b.chars().count()) - same
+}
+
+/// Pick the config section matching `board_name` out of `sections`
+pub fn match_board_section(board_name: String, sections: &[String]) -> Result<String, IswError> {
+    let id = board_id(board_name.as_str());
+
+    if let Some(section) = sections.iter().find(|section| section.to_uppercase() == id) {
+        return Ok(section.clone());
+    }
+
+    let matching: Vec<&String> = sections.iter()
+        .filter(|section| !id.is_empty() && section.to_uppercase().starts_with(id.as_str()))
+        .collect();
+    match matching.len() {
+        1 => return Ok(matching[0].clone()),
+        0 => {}
+        _ => {
+            let names: Vec<&str> = matching.iter().map(|section| section.as_str()).collect();
+            return Err(IswError::UnsupportedBoard("Board <".to_string() + board_name.as_str()
+                + "> matches several sections: " + names.join(", ").as_str() + "; choose one explicitly"));
+        }
+    }
+
+    let prefix: String = id.chars().take(BOARD_ID_LEN).collect();
+    let near: Vec<&str> = sections.iter()
+        .filter(|section| {
+            let section_prefix: String = section.to_uppercase().chars().take(BOARD_ID_LEN).collect();
+            count_differences(section_prefix.as_str(), prefix.as_str()) <= 1
+        })
+        .map(|section| section.as_str())
+        .collect();
+    if near.is_empty() {
+        return Err(IswError::UnsupportedBoard("Board <".to_string() + board_name.as_str() + "> is not supported"));
+    }
+    Err(IswError::UnsupportedBoard("Board <".to_string() + board_name.as_str() + "> is not supported; near matches: "
+        + near.join(", ").as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isw_config_ops::IswConfigOps;
+
+    fn builtin_sections() -> Vec<String> {
+        IswConfigOps::from_text(include_str!("../isw.conf")).get_sections()
+    }
+
+    fn sysfs_root(test: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("isw-rs-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn exact_match_wins() {
+        let sections = builtin_sections();
+        assert_eq!(match_board_section("MS-16Q2EMS1".to_string(), &sections), Ok("16Q2EMS1".to_string()));
+        assert_eq!(match_board_section("ms-16j9ems1".to_string(), &sections), Ok("16J9EMS1".to_string()));
+    }
+
+    #[test]
+    fn unique_prefix_matches() {
+        assert_eq!(match_board_section("MS-16J9".to_string(), &builtin_sections()), Ok("16J9EMS1".to_string()));
+    }
+
+    #[test]
+    fn ambiguous_prefix_lists_candidates() {
+        assert_eq!(match_board_section("MS-16Q2".to_string(), &builtin_sections()),
+                   Err(IswError::UnsupportedBoard("Board <MS-16Q2> matches several sections: 16Q2EMS1, 16Q2EMS2, \
+                                                   16Q2EWS1; choose one explicitly".to_string())));
+    }
+
+    #[test]
+    fn unknown_board_lists_near_matches() {
+        match match_board_section("MS-16J8".to_string(), &builtin_sections()) {
+            Err(IswError::UnsupportedBoard(message)) => {
+                assert!(message.starts_with("Board <MS-16J8> is not supported; near matches: "));
+                assert!(message.contains("16J9EMS1"));
+                assert!(!message.contains("16Q2EMS1"));
+            }
+            other => panic!("expected an unsupported board, got {:?}", other),
+        }
+        assert_eq!(match_board_section("MS-9999".to_string(), &builtin_sections()),
+                   Err(IswError::UnsupportedBoard("Board <MS-9999> is not supported".to_string())));
+    }
+
+    #[test]
+    fn board_name_is_read_below_sysfs_root() {
+        let root = sysfs_root("board-name");
+        std::fs::create_dir_all(root.join("class/dmi/id")).unwrap();
+        std::fs::write(root.join(BOARD_NAME_FILE), "MS-16J9\n").unwrap();
+        assert_eq!(read_board_name(root.to_string_lossy().to_string()), Ok("MS-16J9".to_string()));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn missing_board_name_is_unsupported() {
+        let root = sysfs_root("no-board-name");
+        match read_board_name(root.to_string_lossy().to_string()) {
+            Err(IswError::UnsupportedBoard(message)) => assert!(message.contains("class/dmi/id/board_name")),
+            other => panic!("expected an unsupported board, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use regex::Regex;
+use crate::isw_config_model::{FanCurve, FanKind, IswConfig};
+use crate::isw_config_ops::IswConfigOps;
+use crate::isw_raw_access::IswRegisterWidth;
+use crate::isw_error::IswError;
+
+const ADDRESS_PROFILE: &str = "address_profile";
+const INHERITS: &str = "inherits";
+const FORMAT_SUFFIX: &str = "_format";
+const MAX_ADDRESS: u64 = 0xff;
+
+/// Section headers of `text` in file order, duplicates included; commented lines are skipped
+fn scan_sections(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with('[') && line.ends_with(']'))
+        .map(|line| line[1..line.len() - 1].trim().to_string())
+        .collect()
+}
+
+fn is_address_key(key: &str) -> bool {
+    let re = Regex::new("_address(_[0-9]+)?$").unwrap();
+    re.is_match(key)
+}
+
+fn is_curve_key(key: &str) -> bool {
+    let re = Regex::new("^(cpu|gpu)_(temp|fan_speed)_[0-9]+$").unwrap();
+    re.is_match(key)
+}
+
+fn check_curve(ops: &IswConfigOps, section: &str, kind: FanKind, problems: &mut Vec<String>) {
+    let prefix = "[".to_string() + section + "] ";
+    let mut last_temp: Option<(String, u64)> = None;
+
+    for i in 0..FanCurve::TEMP_POINTS {
+        let key = FanCurve::temp_key(kind, i);
+        match ops.get_numeric_property(section.to_string(), key.clone()) {
+            Err(_) => {
+                if ops.get_property(section.to_string(), key.clone()).is_err() {
+                    problems.push(prefix.clone() + "missing curve point <" + key.as_str() + ">");
+                }
+            }
+            Ok(temp) => {
+                if temp > FanCurve::MAX_TEMP as u64 {
+                    problems.push(prefix.clone() + "<" + key.as_str() + " = " + temp.to_string().as_str()
+                        + "> is outside of 0.." + FanCurve::MAX_TEMP.to_string().as_str());
+                }
+                if let Some((last_key, last)) = &last_temp {
+                    if temp < *last {
+                        problems.push(prefix.clone() + "<" + key.as_str() + " = " + temp.to_string().as_str()
+                            + "> is lower than <" + last_key.as_str() + " = " + last.to_string().as_str() + ">");
+                    }
+                }
+                last_temp = Some((key, temp));
+            }
+        }
+    }
+
+    for i in 0..FanCurve::SPEED_POINTS {
+        let key = FanCurve::speed_key(kind, i);
+        match ops.get_numeric_property(section.to_string(), key.clone()) {
+            Err(_) => {
+                if ops.get_property(section.to_string(), key.clone()).is_err() {
+                    problems.push(prefix.clone() + "missing curve point <" + key.as_str() + ">");
+                }
+            }
+            Ok(speed) => {
+                if speed > FanCurve::MAX_SPEED as u64 {
+                    problems.push(prefix.clone() + "<" + key.as_str() + " = " + speed.to_string().as_str()
+                        + "> is outside of 0.." + FanCurve::MAX_SPEED.to_string().as_str());
+                }
+            }
+        }
+    }
+}
+
+/// Every problem found in the layered config on top of `cfg_file`, one message per problem
+pub fn check_config(cfg_file: Option<String>) -> Result<Vec<String>, IswError> {
+    let mut ops = IswConfigOps::new(cfg_file);
+    ops.load_config()?;
+    Ok(check_layers(&ops))
+}
+
+fn check_layers(ops: &IswConfigOps) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut sections = Vec::new();
+    for layer in ops.get_layers() {
+        let mut seen_in_layer: HashMap<String, usize> = HashMap::new();
+        for section in scan_sections(layer.text.as_str()) {
+            let count = seen_in_layer.entry(section.clone()).or_insert(0);
+            *count += 1;
+            if *count == 2 {
+                problems.push("[".to_string() + section.as_str() + "] duplicate section in <"
+                    + layer.origin.as_str() + ">");
+            }
+            let count = seen.entry(section.clone()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                sections.push(section);
+            }
+        }
+    }
+
+    for section in sections.iter() {
+        let prefix = "[".to_string() + section.as_str() + "] ";
+        let properties = match ops.get_section(section.clone()) {
+            Ok(properties) => properties,
+            Err(error) => {
+                problems.push(prefix.clone() + error.to_string().as_str());
+                continue;
+            }
+        };
+        let mut keys: Vec<&String> = properties.keys().collect();
+        keys.sort();
+
+        for key in keys.iter() {
+            let value = properties[key.as_str()].clone();
+            if key.as_str() == ADDRESS_PROFILE || key.as_str() == INHERITS {
+                if !seen.contains_key(value.as_str()) {
+                    problems.push(prefix.clone() + key.as_str() + " <" + value.as_str() + "> does not exist");
+                }
+            } else if key.ends_with(FORMAT_SUFFIX) {
+                if let Err(error) = IswRegisterWidth::parse(value.as_str()) {
+                    problems.push(prefix.clone() + error.to_string().as_str());
+                }
+            } else {
+                match ops.get_numeric_property(section.clone(), key.to_string()) {
+                    Err(error) => problems.push(prefix.clone() + error.to_string().as_str()),
+                    Ok(number) => {
+                        if is_address_key(key.as_str()) {
+                            if number > MAX_ADDRESS {
+                                problems.push(prefix.clone() + "<" + key.as_str() + " = " + value.as_str()
+                                    + "> is outside of the EC");
+                            }
+                        } else if !is_curve_key(key.as_str()) && number > u8::MAX as u64 {
+                            problems.push(prefix.clone() + "<" + key.as_str() + " = " + value.as_str()
+                                + "> does not fit into a register");
+                        }
+                    }
+                }
+            }
+        }
+
+        if keys.iter().any(|key| is_curve_key(key.as_str())) {
+            check_curve(ops, section.as_str(), FanKind::Cpu, &mut problems);
+            check_curve(ops, section.as_str(), FanKind::Gpu, &mut problems);
+        }
+    }
+
+    // load and validate the way every other command does, so check never passes a config they
+    // refuse; only what the lint above did not already report is added
+    match IswConfig::load(ops) {
+        Err(error) => {
+            if problems.is_empty() {
+                problems.push(error.to_string());
+            }
+        }
+        Ok(config) => {
+            for laptop in config.laptops.values() {
+                let prefix = "[".to_string() + laptop.name.as_str() + "] ";
+                if problems.iter().any(|problem| problem.starts_with(prefix.as_str())) {
+                    continue;
+                }
+                for kind in [FanKind::Cpu, FanKind::Gpu].iter() {
+                    if let Err(error) = laptop.curve(*kind).validate() {
+                        problems.push(prefix.clone() + kind.prefix() + " curve: " + error.to_string().as_str());
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(layer: &str) -> Vec<String> {
+        let mut ops = IswConfigOps::from_text(include_str!("../isw.conf"));
+        ops.add_layer(layer);
+        check_layers(&ops)
+    }
+
+    fn assert_problem(problems: &[String], expected: &str) {
+        assert!(problems.iter().any(|problem| problem == expected), "<{}> not in {:?}", expected, problems);
+    }
+
+    #[test]
+    fn builtin_config_is_clean() {
+        assert_eq!(check(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn flags_missing_address_profile() {
+        let problems = check("[16J9EMS1]\naddress_profile = MSI_ADDRESS_NONE\n");
+        assert_problem(&problems, "[16J9EMS1] address_profile <MSI_ADDRESS_NONE> does not exist");
+    }
+
+    #[test]
+    fn flags_falling_temperatures() {
+        let problems = check("[16J9EMS1]\ncpu_temp_3 = 50\n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("[16J9EMS1] <cpu_temp_3 = 50> is lower than <cpu_temp_2 = "), "{:?}", problems);
+    }
+
+    #[test]
+    fn flags_temperature_above_max() {
+        let problems = check("[16J9EMS1]\ncpu_temp_5 = 120\n");
+        assert_eq!(problems, vec!["[16J9EMS1] <cpu_temp_5 = 120> is outside of 0..100".to_string()]);
+        let problems = check("[16J9EMS1]\ncpu_temp_5 = 300\n");
+        assert_eq!(problems, vec!["[16J9EMS1] <cpu_temp_5 = 300> is outside of 0..100".to_string()]);
+    }
+
+    #[test]
+    fn flags_fan_speed_above_max() {
+        let problems = check("[16J9EMS1]\ngpu_fan_speed_6 = 151\n");
+        assert_eq!(problems, vec!["[16J9EMS1] <gpu_fan_speed_6 = 151> is outside of 0..150".to_string()]);
+    }
+
+    #[test]
+    fn flags_value_too_wide_for_register() {
+        let problems = check("[COOLER_BOOST]\ncooler_boost_on = 0x180\n");
+        assert_eq!(problems, vec!["[COOLER_BOOST] <cooler_boost_on = 0x180> does not fit into a register".to_string()]);
+    }
+
+    #[test]
+    fn flags_missing_curve_points() {
+        let problems = check("[99XXEMS1]\naddress_profile = MSI_ADDRESS_DEFAULT\ncpu_temp_0 = 50\n");
+        assert_problem(&problems, "[99XXEMS1] missing curve point <cpu_temp_1>");
+        assert_problem(&problems, "[99XXEMS1] missing curve point <gpu_fan_speed_6>");
+    }
+
+    #[test]
+    fn flags_duplicate_sections() {
+        let problems = check("[16J9EMS1]\nfan_mode = 140\n[16J9EMS1]\nfan_mode = 12\n");
+        assert_eq!(problems, vec!["[16J9EMS1] duplicate section in <<test layer 1>>".to_string()]);
+    }
+
+    #[test]
+    fn flags_unparsable_values() {
+        let problems = check("[16J9EMS1]\nbattery_charging_threshold = eighty\ncpu_temp_address_0 = 0xzz\n");
+        assert_problem(&problems, "[16J9EMS1] Could not parse <eighty> of <battery_charging_threshold> of <16J9EMS1>");
+        assert_problem(&problems, "[16J9EMS1] Could not parse <0xzz> of <cpu_temp_address_0> of <16J9EMS1>");
+    }
+
+    #[test]
+    fn flags_address_outside_of_ec() {
+        let problems = check("[MSI_ADDRESS_DEFAULT]\nfan_mode_address = 0x100\n");
+        assert_problem(&problems, "[MSI_ADDRESS_DEFAULT] <fan_mode_address = 0x100> is outside of the EC");
+    }
+
+    #[test]
+    fn reports_what_only_loading_catches() {
+        // 16J9EMS1 exists, but is a board section, not an address profile
+        let problems = check("[COOLER_BOOST]\naddress_profile = 16J9EMS1\n");
+        assert_eq!(problems, vec!["Could not get <fan_mode_address> of <16J9EMS1>".to_string()]);
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::isw_config_ops::IswConfigOps;
+use crate::isw_raw_access::IswRegisterWidth;
+use crate::isw_error::IswError;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FanKind {
+    Cpu,
+    Gpu,
+}
+
+impl FanKind {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            FanKind::Cpu => "cpu",
+            FanKind::Gpu => "gpu",
+        }
+    }
+}
+
+/// Temperature thresholds and the fan speeds applied between them
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FanCurve {
+    pub temps: Vec<u8>,
+    pub speeds: Vec<u8>,
+}
+
+impl FanCurve {
+    pub const TEMP_POINTS: usize = 6;
+    pub const SPEED_POINTS: usize = 7;
+    pub const MAX_TEMP: u8 = 100;
+    pub const MAX_SPEED: u8 = 150;
+
+    pub fn temp_key(kind: FanKind, index: usize) -> String {
+        kind.prefix().to_string() + "_temp_" + index.to_string().as_str()
+    }
+
+    pub fn speed_key(kind: FanKind, index: usize) -> String {
+        kind.prefix().to_string() + "_fan_speed_" + index.to_string().as_str()
+    }
+
+    /// Key of the register holding `key`, "cpu_temp_3" becomes "cpu_temp_address_3"
+    pub fn address_key(key: String) -> String {
+        let pos = key.rfind('_').unwrap();
+        key[..pos].to_string() + "_address_" + &key[pos + 1..]
+    }
+
+    /// Point counts, value ranges and rising temperatures, checked before anything is written
+    pub fn validate(&self) -> Result<(), IswError> {
+        if self.temps.len() != FanCurve::TEMP_POINTS || self.speeds.len() != FanCurve::SPEED_POINTS {
+            return Err(IswError::OutOfRange("Fan curve needs ".to_string() + FanCurve::TEMP_POINTS.to_string().as_str()
+                + " temperatures and " + FanCurve::SPEED_POINTS.to_string().as_str() + " fan speeds"));
+        }
+        for (i, temp) in self.temps.iter().enumerate() {
+            if *temp > FanCurve::MAX_TEMP {
+                return Err(IswError::OutOfRange("Temperature <".to_string() + temp.to_string().as_str()
+                    + "> of fan curve point " + i.to_string().as_str() + " is outside of 0.."
+                    + FanCurve::MAX_TEMP.to_string().as_str()));
+            }
+            if i > 0 && *temp < self.temps[i - 1] {
+                return Err(IswError::OutOfRange("Temperature <".to_string() + temp.to_string().as_str()
+                    + "> of fan curve point " + i.to_string().as_str() + " is lower than the one before it <"
+                    + self.temps[i - 1].to_string().as_str() + ">"));
+            }
+        }
+        for (i, speed) in self.speeds.iter().enumerate() {
+            if *speed > FanCurve::MAX_SPEED {
+                return Err(IswError::OutOfRange("Fan speed <".to_string() + speed.to_string().as_str()
+                    + "> of fan curve point " + i.to_string().as_str() + " is outside of 0.."
+                    + FanCurve::MAX_SPEED.to_string().as_str()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Location of a single EC register
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RegisterSpec {
+    pub address: u64,
+    pub width: IswRegisterWidth,
+}
+
+/// Location of a fixed-length string in the EC
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StringSpec {
+    pub address: u64,
+    pub length: usize,
+}
+
+/// Registers of the fan curve of one fan
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FanCurveAddresses {
+    pub temps: Vec<RegisterSpec>,
+    pub speeds: Vec<RegisterSpec>,
+}
+
+/// Register layout of an EC, e.g. [MSI_ADDRESS_DEFAULT]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AddressProfile {
+    pub name: String,
+    pub fan_mode: RegisterSpec,
+    pub cooler_boost: RegisterSpec,
+    pub usb_backlight: RegisterSpec,
+    pub battery_charging_threshold: RegisterSpec,
+    pub realtime_cpu_temp: RegisterSpec,
+    pub realtime_cpu_fan_speed: RegisterSpec,
+    pub realtime_cpu_fan_rpm: RegisterSpec,
+    pub realtime_gpu_temp: RegisterSpec,
+    pub realtime_gpu_fan_speed: RegisterSpec,
+    pub realtime_gpu_fan_rpm: RegisterSpec,
+    pub cpu_curve: FanCurveAddresses,
+    pub gpu_curve: FanCurveAddresses,
+    pub fw_version: StringSpec,
+    pub fw_date: StringSpec,
+    pub fw_time: StringSpec,
+}
+
+impl AddressProfile {
+    pub fn curve(&self, kind: FanKind) -> &FanCurveAddresses {
+        match kind {
+            FanKind::Cpu => &self.cpu_curve,
+            FanKind::Gpu => &self.gpu_curve,
+        }
+    }
+
+    /// Every register isw writes to, named by its config key
+    pub fn writable_registers(&self) -> Vec<(String, RegisterSpec)> {
+        let mut registers = vec![
+            ("fan_mode_address".to_string(), self.fan_mode),
+            ("cooler_boost_address".to_string(), self.cooler_boost),
+            ("usb_backlight_address".to_string(), self.usb_backlight),
+            ("battery_charging_threshold_address".to_string(), self.battery_charging_threshold),
+        ];
+        for kind in [FanKind::Cpu, FanKind::Gpu].iter() {
+            let addresses = self.curve(*kind);
+            for (i, register) in addresses.temps.iter().enumerate() {
+                registers.push((FanCurve::address_key(FanCurve::temp_key(*kind, i)), *register));
+            }
+            for (i, register) in addresses.speeds.iter().enumerate() {
+                registers.push((FanCurve::address_key(FanCurve::speed_key(*kind, i)), *register));
+            }
+        }
+        registers
+    }
+}
+
+/// Default values of a laptop board, e.g. [16J9EMS1]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LaptopProfile {
+    pub name: String,
+    pub address_profile: String,
+    pub fan_mode: u8,
+    pub battery_charging_threshold: u8,
+    pub cpu: FanCurve,
+    pub gpu: FanCurve,
+}
+
+impl LaptopProfile {
+    pub fn curve(&self, kind: FanKind) -> &FanCurve {
+        match kind {
+            FanKind::Cpu => &self.cpu,
+            FanKind::Gpu => &self.gpu,
+        }
+    }
+}
+
+/// Register values of [COOLER_BOOST]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CoolerBoostSpec {
+    pub address_profile: String,
+    pub off: u8,
+    pub on: u8,
+}
+
+/// Register values of [USB_BACKLIGHT]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct UsbBacklightSpec {
+    pub address_profile: String,
+    pub off: u8,
+    pub half: u8,
+    pub full: u8,
+}
+
+/// Register values of [FAN_MODE]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FanModeSpec {
+    pub address_profile: String,
+    pub advanced: u8,
+    pub basic: u8,
+    pub auto: u8,
+}
+
+/// The whole isw.conf, loaded and validated once
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IswConfig {
+    pub address_profiles: BTreeMap<String, AddressProfile>,
+    pub laptops: BTreeMap<String, LaptopProfile>,
+    pub cooler_boost: CoolerBoostSpec,
+    pub usb_backlight: UsbBacklightSpec,
+    pub fan_mode: FanModeSpec,
+}
+
+impl IswConfig {
+    pub const MSI_ADDRESS_DEFAULT: &'static str = "MSI_ADDRESS_DEFAULT";
+    const COOLER_BOOST: &'static str = "COOLER_BOOST";
+    const USB_BACKLIGHT: &'static str = "USB_BACKLIGHT";
+    const FAN_MODE: &'static str = "FAN_MODE";
+    const FORMAT_SUFFIX: &'static str = "_format";
+
+    /// Build the typed model from `ops`, failing on the first missing or invalid key
+    pub fn load(ops: &IswConfigOps) -> Result<IswConfig, IswError> {
+        let mut address_profiles = BTreeMap::new();
+        let mut laptops = BTreeMap::new();
+
+        for section in ops.get_sections() {
+            ops.get_inheritance_chain(section.clone())?;
+            let profile = match ops.get_address_profile(section.clone()) {
+                Ok(profile) => profile,
+                Err(_) => continue,
+            };
+            if !address_profiles.contains_key(profile.as_str()) {
+                let loaded = IswConfig::load_address_profile(ops, profile.clone())?;
+                address_profiles.insert(profile.clone(), loaded);
+            }
+            if ops.get_property(section.clone(), FanCurve::temp_key(FanKind::Cpu, 0)).is_ok() {
+                let laptop = IswConfig::load_laptop_profile(ops, section.clone(), profile)?;
+                laptops.insert(section, laptop);
+            }
+        }
+
+        let cooler_boost = CoolerBoostSpec {
+            address_profile: IswConfig::get_address_profile(ops, IswConfig::COOLER_BOOST, &address_profiles)?,
+            off: IswConfig::get_u8(ops, IswConfig::COOLER_BOOST, "cooler_boost_off")?,
+            on: IswConfig::get_u8(ops, IswConfig::COOLER_BOOST, "cooler_boost_on")?,
+        };
+        let usb_backlight = UsbBacklightSpec {
+            address_profile: IswConfig::get_address_profile(ops, IswConfig::USB_BACKLIGHT, &address_profiles)?,
+            off: IswConfig::get_u8(ops, IswConfig::USB_BACKLIGHT, "usb_backlight_off")?,
+            half: IswConfig::get_u8(ops, IswConfig::USB_BACKLIGHT, "usb_backlight_half")?,
+            full: IswConfig::get_u8(ops, IswConfig::USB_BACKLIGHT, "usb_backlight_full")?,
+        };
+        let fan_mode = FanModeSpec {
+            address_profile: IswConfig::get_address_profile(ops, IswConfig::FAN_MODE, &address_profiles)?,
+            advanced: IswConfig::get_u8(ops, IswConfig::FAN_MODE, "fan_mode_advanced")?,
+            basic: IswConfig::get_u8(ops, IswConfig::FAN_MODE, "fan_mode_basic")?,
+            auto: IswConfig::get_u8(ops, IswConfig::FAN_MODE, "fan_mode_auto")?,
+        };
+
+        if !address_profiles.contains_key(IswConfig::MSI_ADDRESS_DEFAULT) {
+            return Err(IswError::Config("Missing address profile <".to_string() + IswConfig::MSI_ADDRESS_DEFAULT + ">"));
+        }
+
+        Ok(IswConfig {
+            address_profiles,
+            laptops,
+            cooler_boost,
+            usb_backlight,
+            fan_mode,
+        })
+    }
+
+    /// Address profile named `name`
+    pub fn address_profile(&self, name: &str) -> Result<&AddressProfile, IswError> {
+        match self.address_profiles.get(name) {
+            None => Err(IswError::Config("Unknown address profile <".to_string() + name + ">")),
+            Some(profile) => Ok(profile),
+        }
+    }
+
+    /// Laptop board section named `name`
+    pub fn laptop(&self, name: &str) -> Result<&LaptopProfile, IswError> {
+        match self.laptops.get(name) {
+            None => Err(IswError::Config("Unknown laptop section <".to_string() + name + ">")),
+            Some(laptop) => Ok(laptop),
+        }
+    }
+
+    fn get_address_profile(ops: &IswConfigOps, section: &str, address_profiles: &BTreeMap<String, AddressProfile>) -> Result<String, IswError> {
+        let profile = ops.get_address_profile(section.to_string())?;
+        if !address_profiles.contains_key(profile.as_str()) {
+            return Err(IswError::Config("Section <".to_string() + section + "> refers to unknown address profile <"
+                + profile.as_str() + ">"));
+        }
+        Ok(profile)
+    }
+
+    fn get_u8(ops: &IswConfigOps, section: &str, key: &str) -> Result<u8, IswError> {
+        let value = ops.get_numeric_property(section.to_string(), key.to_string())?;
+        if value > u8::MAX as u64 {
+            return Err(IswError::Parse("Value <".to_string() + value.to_string().as_str() + "> of <" + key
+                + "> in <" + section + "> does not fit into a register"));
+        }
+        Ok(value as u8)
+    }
+
+    fn get_register(ops: &IswConfigOps, section: &str, key: &str) -> Result<RegisterSpec, IswError> {
+        let address = ops.get_numeric_property(section.to_string(), key.to_string())?;
+        let format_key = key.to_string() + IswConfig::FORMAT_SUFFIX;
+        let width = match ops.get_property(section.to_string(), format_key) {
+            Err(_) => IswRegisterWidth::U8,
+            Ok(width) => match IswRegisterWidth::parse(width.as_str()) {
+                Ok(width) => width,
+                Err(error) => {
+                    return Err(IswError::Parse(error.to_string() + " in <" + key + IswConfig::FORMAT_SUFFIX
+                        + "> of <" + section + ">"));
+                }
+            },
+        };
+        Ok(RegisterSpec { address, width })
+    }
+
+    fn get_string_spec(ops: &IswConfigOps, section: &str, name: &str) -> Result<StringSpec, IswError> {
+        let address = ops.get_numeric_property(section.to_string(), name.to_string() + "_address")?;
+        let length = ops.get_numeric_property(section.to_string(), name.to_string() + "_length")?;
+        Ok(StringSpec { address, length: length as usize })
+    }
+
+    fn get_curve_addresses(ops: &IswConfigOps, section: &str, kind: FanKind) -> Result<FanCurveAddresses, IswError> {
+        let mut addresses = FanCurveAddresses { temps: Vec::new(), speeds: Vec::new() };
+        for i in 0..FanCurve::TEMP_POINTS {
+            let key = FanCurve::address_key(FanCurve::temp_key(kind, i));
+            addresses.temps.push(IswConfig::get_register(ops, section, key.as_str())?);
+        }
+        for i in 0..FanCurve::SPEED_POINTS {
+            let key = FanCurve::address_key(FanCurve::speed_key(kind, i));
+            addresses.speeds.push(IswConfig::get_register(ops, section, key.as_str())?);
+        }
+        Ok(addresses)
+    }
+
+    fn load_address_profile(ops: &IswConfigOps, name: String) -> Result<AddressProfile, IswError> {
+        let section = name.as_str();
+        Ok(AddressProfile {
+            name: name.clone(),
+            fan_mode: IswConfig::get_register(ops, section, "fan_mode_address")?,
+            cooler_boost: IswConfig::get_register(ops, section, "cooler_boost_address")?,
+            usb_backlight: IswConfig::get_register(ops, section, "usb_backlight_address")?,
+            battery_charging_threshold: IswConfig::get_register(ops, section, "battery_charging_threshold_address")?,
+            realtime_cpu_temp: IswConfig::get_register(ops, section, "realtime_cpu_temp_address")?,
+            realtime_cpu_fan_speed: IswConfig::get_register(ops, section, "realtime_cpu_fan_speed_address")?,
+            realtime_cpu_fan_rpm: IswConfig::get_register(ops, section, "realtime_cpu_fan_rpm_address")?,
+            realtime_gpu_temp: IswConfig::get_register(ops, section, "realtime_gpu_temp_address")?,
+            realtime_gpu_fan_speed: IswConfig::get_register(ops, section, "realtime_gpu_fan_speed_address")?,
+            realtime_gpu_fan_rpm: IswConfig::get_register(ops, section, "realtime_gpu_fan_rpm_address")?,
+            cpu_curve: IswConfig::get_curve_addresses(ops, section, FanKind::Cpu)?,
+            gpu_curve: IswConfig::get_curve_addresses(ops, section, FanKind::Gpu)?,
+            fw_version: IswConfig::get_string_spec(ops, section, "fw_version")?,
+            fw_date: IswConfig::get_string_spec(ops, section, "fw_date")?,
+            fw_time: IswConfig::get_string_spec(ops, section, "fw_time")?,
+        })
+    }
+
+    fn get_curve(ops: &IswConfigOps, section: &str, kind: FanKind) -> Result<FanCurve, IswError> {
+        let mut curve = FanCurve { temps: Vec::new(), speeds: Vec::new() };
+        for i in 0..FanCurve::TEMP_POINTS {
+            curve.temps.push(IswConfig::get_u8(ops, section, FanCurve::temp_key(kind, i).as_str())?);
+        }
+        for i in 0..FanCurve::SPEED_POINTS {
+            curve.speeds.push(IswConfig::get_u8(ops, section, FanCurve::speed_key(kind, i).as_str())?);
+        }
+        Ok(curve)
+    }
+
+    fn load_laptop_profile(ops: &IswConfigOps, name: String, address_profile: String) -> Result<LaptopProfile, IswError> {
+        let section = name.as_str();
+        Ok(LaptopProfile {
+            name: name.clone(),
+            address_profile,
+            fan_mode: IswConfig::get_u8(ops, section, "fan_mode")?,
+            battery_charging_threshold: IswConfig::get_u8(ops, section, "battery_charging_threshold")?,
+            cpu: IswConfig::get_curve(ops, section, FanKind::Cpu)?,
+            gpu: IswConfig::get_curve(ops, section, FanKind::Gpu)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_register_reads_format_key() {
+        let ops = IswConfigOps::from_text("[P]\nrealtime_cpu_fan_rpm_address = 0xcc\nrealtime_cpu_fan_rpm_address_format = u16be\n\
+                                           realtime_cpu_temp_address = 0x68\nbad_address = 0x10\nbad_address_format = u24\n");
+        assert_eq!(IswConfig::get_register(&ops, "P", "realtime_cpu_fan_rpm_address"),
+                   Ok(RegisterSpec { address: 0xcc, width: IswRegisterWidth::U16Be }));
+        assert_eq!(IswConfig::get_register(&ops, "P", "realtime_cpu_temp_address"),
+                   Ok(RegisterSpec { address: 0x68, width: IswRegisterWidth::U8 }));
+        assert!(matches!(IswConfig::get_register(&ops, "P", "bad_address"), Err(IswError::Parse(_))));
+    }
+
+    #[test]
+    fn builtin_config_reads_fan_rpm_big_endian() {
+        let config = IswConfig::load(&IswConfigOps::from_text(include_str!("../isw.conf"))).unwrap();
+        let profile = config.address_profile(IswConfig::MSI_ADDRESS_DEFAULT).unwrap();
+        assert_eq!(profile.realtime_cpu_fan_rpm, RegisterSpec { address: 0xcc, width: IswRegisterWidth::U16Be });
+        assert_eq!(profile.realtime_gpu_fan_rpm, RegisterSpec { address: 0xca, width: IswRegisterWidth::U16Be });
+        assert_eq!(profile.realtime_cpu_temp.width, IswRegisterWidth::U8);
+    }
+}
@@ -1,59 +1,335 @@
+use std::collections::HashMap;
+use std::path::Path;
 use regex::Regex;
-use configparser::ini::Ini;
+use crate::isw_error::IswError;
 
-pub struct IswConfigOps {
-    m_cfg_file: String,
-    m_cfg_parser: Ini,
+type IniMap = HashMap<String, HashMap<String, String>>;
+
+/// One config source, e.g. the built-in isw.conf or /etc/isw-rs/isw.conf
+#[derive(Clone)]
+pub struct IswConfigLayer {
+    pub origin: String,
+    pub text: String,
+    m_values: IniMap,
 }
 
-impl Clone for IswConfigOps {
-    fn clone(&self) -> IswConfigOps {
-      IswConfigOps::new(self.m_cfg_file.clone())
+impl IswConfigLayer {
+    fn new(origin: String, text: String) -> IswConfigLayer {
+        let values = IswConfigLayer::parse(text.as_str());
+        IswConfigLayer {
+            origin,
+            text,
+            m_values: values,
+        }
     }
+
+    /// Lines starting with '#' or ';' are comments, a line holding only `[...]` starts a section,
+    /// any other line holding '=' is a key/value pair; keys before the first section go to
+    /// "DEFAULT", later duplicates win
+    fn parse(text: &str) -> IniMap {
+        let mut map = IniMap::new();
+        let mut section = "DEFAULT".to_string();
+        for line in text.lines() {
+            if IswConfigOps::is_comment(line) {
+                continue;
+            }
+            if let Some(header) = IswConfigOps::parse_section_header(line) {
+                section = header.to_string();
+                continue;
+            }
+            let trimmed = line.trim();
+            if let Some(delimiter) = trimmed.find('=') {
+                map.entry(section.clone()).or_default()
+                    .insert(trimmed[..delimiter].trim().to_string(), trimmed[delimiter + 1..].trim().to_string());
+            }
+        }
+        map
+    }
+}
+
+#[derive(Clone)]
+pub struct IswConfigOps {
+    m_cfg_file: Option<String>,
+    m_layers: Vec<IswConfigLayer>,
+    m_cfg_lines: Vec<String>,
 }
 
 impl IswConfigOps {
     const ADDRESS_PROFILE: &'static str = "address_profile";
+    const INHERITS: &'static str = "inherits";
+    const BUILTIN_CONFIG: &'static str = include_str!("../isw.conf");
+    const BUILTIN_ORIGIN: &'static str = "<built-in>";
+    const SYSTEM_CONFIG_DIR: &'static str = "/etc/isw-rs";
+    const USER_CONFIG_DIR: &'static str = "isw-rs";
+    const CONFIG_FILE_NAME: &'static str = "isw.conf";
 
-    pub fn new(cfg_file: String) -> IswConfigOps {
+    /// Config made of the built-in isw.conf, /etc/isw-rs/isw.conf, $XDG_CONFIG_HOME/isw-rs/isw.conf
+    /// and `cfg_file`, later layers overriding single keys of earlier ones
+    pub fn new(cfg_file: Option<String>) -> IswConfigOps {
         IswConfigOps {
             m_cfg_file: cfg_file,
-            m_cfg_parser: Ini::new(),
+            m_layers: Vec::new(),
+            m_cfg_lines: Vec::new(),
         }
     }
 
-    fn format_not_found_error(&self, x: String, y: String) -> String {
-        "Could not get <".to_string() + y.as_str() + "> of <"
-            + x.as_str() + ">"
+    /// Config made of `text` alone, which is also the file `set_property` edits
+    #[cfg(test)]
+    pub fn from_text(text: &str) -> IswConfigOps {
+        IswConfigOps {
+            m_cfg_file: None,
+            m_layers: vec![IswConfigLayer::new("<test>".to_string(), text.to_string())],
+            m_cfg_lines: text.lines().map(|line| line.to_string()).collect(),
+        }
     }
 
-    pub fn load_config(&mut self) -> Result<(), String> {
-        self.m_cfg_parser = Ini::new();
-        self.m_cfg_parser.load(self.m_cfg_file.as_str())?;
+    /// Add `text` on top of the other layers, like a --config file
+    #[cfg(test)]
+    pub fn add_layer(&mut self, text: &str) {
+        let origin = "<test layer ".to_string() + self.m_layers.len().to_string().as_str() + ">";
+        self.m_layers.push(IswConfigLayer::new(origin, text.to_string()));
+    }
+
+    fn not_found_error(&self, key: String, section: String) -> IswError {
+        IswError::ConfigKeyMissing { section, key }
+    }
+
+    fn parse_error(&self, value: String, key: String, section: String) -> IswError {
+        IswError::Parse("Could not parse <".to_string() + value.as_str() + "> of <" + key.as_str() + "> of <"
+            + section.as_str() + ">")
+    }
+
+    fn io_error(&self, action: &str, file: &str, error: std::io::Error) -> IswError {
+        IswError::from_io(action.to_string() + " file <" + file + "> failed with <"
+            + error.to_string().as_str() + ">", &error, IswError::Config)
+    }
+
+    fn system_config_file() -> String {
+        Path::new(IswConfigOps::SYSTEM_CONFIG_DIR).join(IswConfigOps::CONFIG_FILE_NAME)
+            .to_string_lossy().to_string()
+    }
+
+    /// $XDG_CONFIG_HOME/isw-rs/isw.conf, falling back to ~/.config if XDG_CONFIG_HOME is unset
+    fn user_config_file() -> Option<String> {
+        let config_home = match std::env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if !dir.is_empty() => Path::new(dir.as_str()).to_path_buf(),
+            _ => match std::env::var("HOME") {
+                Ok(home) if !home.is_empty() => Path::new(home.as_str()).join(".config"),
+                _ => return None,
+            }
+        };
+        Some(config_home.join(IswConfigOps::USER_CONFIG_DIR).join(IswConfigOps::CONFIG_FILE_NAME)
+            .to_string_lossy().to_string())
+    }
+
+    /// File edited by `set_property`: the one given with --config, else the user's config file
+    fn write_file(&self) -> Result<String, IswError> {
+        match &self.m_cfg_file {
+            Some(file) => Ok(file.clone()),
+            None => match IswConfigOps::user_config_file() {
+                Some(file) => Ok(file),
+                None => Err(IswError::Config("Could not locate the user config file; neither XDG_CONFIG_HOME nor HOME is set".to_string())),
+            }
+        }
+    }
+
+    fn load_layer(&self, file: &str, required: bool) -> Result<Option<IswConfigLayer>, IswError> {
+        match std::fs::read_to_string(file) {
+            Ok(text) => Ok(Some(IswConfigLayer::new(file.to_string(), text))),
+            Err(error) if !required && error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(self.io_error("Reading", file, error)),
+        }
+    }
+
+    fn is_comment(line: &str) -> bool {
+        let line = line.trim_start();
+        line.starts_with('#') || line.starts_with(';')
+    }
+
+    /// Section name if `line` is a section header
+    fn parse_section_header(line: &str) -> Option<&str> {
+        let line = line.trim();
+        if line.len() >= 2 && line.starts_with('[') && line.ends_with(']') {
+            return Some(line[1..line.len() - 1].trim());
+        }
+        None
+    }
+
+    /// Key name if `line` is a key/value line
+    fn parse_key(line: &str) -> Option<&str> {
+        if IswConfigOps::is_comment(line) {
+            return None;
+        }
+        line.find('=').map(|index| line[..index].trim())
+    }
+
+    pub fn load_config(&mut self) -> Result<(), IswError> {
+        let mut layers = vec![IswConfigLayer::new(IswConfigOps::BUILTIN_ORIGIN.to_string(),
+                                                  IswConfigOps::BUILTIN_CONFIG.to_string())];
+        let mut files = vec![(IswConfigOps::system_config_file(), false)];
+        if let Some(file) = IswConfigOps::user_config_file() {
+            files.push((file, false));
+        }
+        if let Some(file) = &self.m_cfg_file {
+            files.push((file.clone(), true));
+        }
+        for (file, required) in files {
+            if layers.iter().any(|layer| layer.origin == file) {
+                continue;
+            }
+            if let Some(layer) = self.load_layer(file.as_str(), required)? {
+                layers.push(layer);
+            }
+        }
+
+        let write_file = self.write_file().ok();
+        self.m_cfg_lines = match layers.iter().find(|layer| Some(&layer.origin) == write_file.as_ref()) {
+            Some(layer) => layer.text.lines().map(|line| line.to_string()).collect(),
+            None => Vec::new(),
+        };
+        self.m_layers = layers;
         Ok(())
     }
 
-    pub fn get_numeric_property(&self, section: String, key: String) -> Result<u64, String> {
-        match self.m_cfg_parser.get(section.as_str(),
-                                    key.as_str()) {
-            None => Err(self.format_not_found_error(key, section)),
-            Some(e) => {
-                let re = Regex::new("0[xX][0-9a-fA-F]+").unwrap();
-                return if re.is_match(e.as_str()) {
-                    let wo_prefix = e.trim_start_matches("0x");
-                    Ok(u64::from_str_radix(wo_prefix, 16).expect("Could not parse value"))
+    /// Config sources in the order they are applied
+    pub fn get_layers(&self) -> &[IswConfigLayer] {
+        &self.m_layers
+    }
+
+    /// Write the edited config file back and reload; comments and layout are kept as they were loaded
+    pub fn save_config(&mut self) -> Result<(), IswError> {
+        let file = self.write_file()?;
+        if let Some(dir) = Path::new(file.as_str()).parent() {
+            if let Err(error) = std::fs::create_dir_all(dir) {
+                return Err(self.io_error("Creating directory of", file.as_str(), error));
+            }
+        }
+        let text = self.m_cfg_lines.join("\n") + "\n";
+        if let Err(error) = std::fs::write(file.as_str(), text) {
+            return Err(self.io_error("Writing", file.as_str(), error));
+        }
+        self.load_config()
+    }
+
+    /// Set `key` of `section` to `value` in the top config file, adding the key or the section if
+    /// they do not exist there yet; getters see the change after `save_config`
+    pub fn set_property(&mut self, section: String, key: String, value: String) -> Result<(), IswError> {
+        let header = self.m_cfg_lines.iter()
+            .position(|line| !IswConfigOps::is_comment(line)
+                && IswConfigOps::parse_section_header(line) == Some(section.as_str()));
+
+        match header {
+            None => {
+                if self.m_cfg_lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    self.m_cfg_lines.push(String::new());
+                }
+                self.m_cfg_lines.push("[".to_string() + section.as_str() + "]");
+                self.m_cfg_lines.push(key.clone() + " = " + value.as_str());
+            }
+            Some(header) => {
+                let end = self.m_cfg_lines.iter().enumerate().skip(header + 1)
+                    .find(|(_, line)| !IswConfigOps::is_comment(line)
+                        && IswConfigOps::parse_section_header(line).is_some())
+                    .map_or(self.m_cfg_lines.len(), |(index, _)| index);
+                let existing = (header + 1..end)
+                    .find(|index| IswConfigOps::parse_key(&self.m_cfg_lines[*index]) == Some(key.as_str()));
+
+                match existing {
+                    Some(index) => {
+                        // keep the key and the spacing around '=' as written
+                        let line = &self.m_cfg_lines[index];
+                        let separator = line.find('=').unwrap() + 1;
+                        let spacing = line[separator..].len() - line[separator..].trim_start().len();
+                        self.m_cfg_lines[index] = line[..separator + spacing].to_string() + value.as_str();
+                    }
+                    None => {
+                        let last_key = (header + 1..end).rev()
+                            .find(|index| IswConfigOps::parse_key(&self.m_cfg_lines[*index]).is_some())
+                            .unwrap_or(header);
+                        self.m_cfg_lines.insert(last_key + 1, key.clone() + " = " + value.as_str());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Value of `key` written in `section` itself and the layer it came from
+    fn lookup_direct(&self, section: &str, key: &str) -> Option<(String, &IswConfigLayer)> {
+        self.m_layers.iter().rev()
+            .find_map(|layer| layer.m_values.get(section)
+                .and_then(|values| values.get(key))
+                .map(|value| (value.clone(), layer)))
+    }
+
+    /// `section` followed by the sections it inherits from, nearest first
+    pub fn get_inheritance_chain(&self, section: String) -> Result<Vec<String>, IswError> {
+        let mut chain = vec![section];
+        while let Some((parent, _)) = self.lookup_direct(chain.last().unwrap(), IswConfigOps::INHERITS) {
+            let cycle = chain.contains(&parent);
+            chain.push(parent);
+            if cycle {
+                return Err(IswError::Config("Inheritance cycle <".to_string() + chain.join(" -> ").as_str() + ">"));
+            }
+        }
+        Ok(chain)
+    }
+
+    /// Effective value of `key` in `section`, following `inherits`, and the layer it came from
+    fn lookup(&self, section: &str, key: &str) -> Result<Option<(String, &IswConfigLayer)>, IswError> {
+        if key == IswConfigOps::INHERITS {
+            return Ok(self.lookup_direct(section, key));
+        }
+        for ancestor in self.get_inheritance_chain(section.to_string())? {
+            if let Some(found) = self.lookup_direct(ancestor.as_str(), key) {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Origin (file name or "<built-in>") of the effective value of `key` in `section`
+    pub fn get_origin(&self, section: String, key: String) -> Result<String, IswError> {
+        match self.lookup(section.as_str(), key.as_str())? {
+            None => Err(self.not_found_error(key, section)),
+            Some((_, layer)) => Ok(layer.origin.clone())
+        }
+    }
+
+    pub fn get_numeric_property(&self, section: String, key: String) -> Result<u64, IswError> {
+        match self.lookup(section.as_str(), key.as_str())? {
+            None => Err(self.not_found_error(key, section)),
+            Some((e, _)) => {
+                let re = Regex::new("^0[xX][0-9a-fA-F]+$").unwrap();
+                let parsed = if re.is_match(e.as_str()) {
+                    u64::from_str_radix(&e[2..], 16)
                 } else {
-                    Ok(u64::from_str_radix(e.as_str(), 10).expect("Could not parse value"))
+                    e.parse::<u64>()
                 };
+                match parsed {
+                    Ok(value) => Ok(value),
+                    Err(_) => Err(self.parse_error(e, key, section))
+                }
             }
         }
     }
 
-    pub fn get_base_address(&self, section: String, address_of: String) -> Result<u64, String> {
-        match self.m_cfg_parser.get(section.as_str(),
-                                    &*IswConfigOps::ADDRESS_PROFILE.to_string()) {
-            None => Err(self.format_not_found_error(address_of, section)),
-            Some(val) => {
+    pub fn get_property(&self, section: String, key: String) -> Result<String, IswError> {
+        match self.lookup(section.as_str(), key.as_str())? {
+            None => Err(self.not_found_error(key, section)),
+            Some((e, _)) => Ok(e)
+        }
+    }
+
+    /// Name of the address profile `section` refers to
+    pub fn get_address_profile(&self, section: String) -> Result<String, IswError> {
+        self.get_property(section, IswConfigOps::ADDRESS_PROFILE.to_string())
+    }
+
+    pub fn get_base_address(&self, section: String, address_of: String) -> Result<u64, IswError> {
+        match self.lookup(section.as_str(), IswConfigOps::ADDRESS_PROFILE)? {
+            None => Err(self.not_found_error(address_of, section)),
+            Some((val, _)) => {
                 match self.get_numeric_property(val, address_of) {
                     Ok(read) => Ok(read),
                     Err(error) => Err(error)
@@ -61,4 +337,105 @@ impl IswConfigOps {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// All effective key/value pairs of `section`, including the ones it inherits
+    pub fn get_section(&self, section: String) -> Result<HashMap<String, String>, IswError> {
+        let mut properties = HashMap::new();
+        for ancestor in self.get_inheritance_chain(section.clone())?.iter().rev() {
+            let mut found = false;
+            for layer in self.m_layers.iter() {
+                if let Some(values) = layer.m_values.get(ancestor.as_str()) {
+                    found = true;
+                    properties.extend(values.iter().map(|(key, value)| (key.clone(), value.clone())));
+                }
+            }
+            if !found {
+                return Err(IswError::Config("Could not get section <".to_string() + ancestor.as_str() + ">"));
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Names of all sections, sorted
+    pub fn get_sections(&self) -> Vec<String> {
+        let mut sections: Vec<String> = self.m_layers.iter()
+            .flat_map(|layer| layer.m_values.keys().cloned())
+            .collect();
+        sections.sort();
+        sections.dedup();
+        sections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(map: &IniMap, section: &str, key: &str) -> Option<String> {
+        map.get(section).and_then(|values| values.get(key)).cloned()
+    }
+
+    #[test]
+    fn parse_skips_comments() {
+        let map = IswConfigLayer::parse("[A]\n# [B]\n; key = commented\nkey = 1\n  # other = 2\n");
+        assert_eq!(value(&map, "A", "key"), Some("1".to_string()));
+        assert_eq!(value(&map, "A", "other"), None);
+        assert!(!map.contains_key("B"));
+        assert!(!map.contains_key("# [B]"));
+    }
+
+    #[test]
+    fn parse_assigns_keys_to_sections() {
+        let map = IswConfigLayer::parse("top = 0\n [ A ] \nkey=1\nkey = 2\n[B]\nkey = a = b\n[C] x\nmore = 3\n");
+        assert_eq!(value(&map, "DEFAULT", "top"), Some("0".to_string()));
+        assert_eq!(value(&map, "A", "key"), Some("2".to_string()));
+        assert_eq!(value(&map, "B", "key"), Some("a = b".to_string()));
+        // not a header, so the keys after it stay in B
+        assert_eq!(value(&map, "B", "more"), Some("3".to_string()));
+        assert!(!map.contains_key("C"));
+    }
+
+    #[test]
+    fn inheritance_chain_follows_inherits() {
+        let ops = IswConfigOps::from_text("[A]\ninherits = B\n[B]\ninherits = C\n[C]\nkey = 1\n");
+        assert_eq!(ops.get_inheritance_chain("A".to_string()),
+                   Ok(vec!["A".to_string(), "B".to_string(), "C".to_string()]));
+        assert_eq!(ops.get_property("A".to_string(), "key".to_string()), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn inheritance_chain_detects_cycles() {
+        let ops = IswConfigOps::from_text("[A]\ninherits = B\n[B]\ninherits = C\n[C]\ninherits = A\n[D]\ninherits = D\n");
+        assert_eq!(ops.get_inheritance_chain("A".to_string()),
+                   Err(IswError::Config("Inheritance cycle <A -> B -> C -> A>".to_string())));
+        assert_eq!(ops.get_inheritance_chain("D".to_string()),
+                   Err(IswError::Config("Inheritance cycle <D -> D>".to_string())));
+        assert!(ops.get_property("B".to_string(), "key".to_string()).is_err());
+    }
+
+    #[test]
+    fn set_property_replaces_value_keeping_layout() {
+        let mut ops = IswConfigOps::from_text("# header\n[A]\nkey =  1 \n# key = 0\nother=2\n");
+        ops.set_property("A".to_string(), "key".to_string(), "5".to_string()).unwrap();
+        ops.set_property("A".to_string(), "other".to_string(), "6".to_string()).unwrap();
+        assert_eq!(ops.m_cfg_lines, vec!["# header", "[A]", "key =  5", "# key = 0", "other=6"]);
+    }
+
+    #[test]
+    fn set_property_adds_key_after_last_key_of_section() {
+        let mut ops = IswConfigOps::from_text("[A]\nkey = 1\n\n# about B\n[B]\nkey = 2\n");
+        ops.set_property("A".to_string(), "new".to_string(), "3".to_string()).unwrap();
+        assert_eq!(ops.m_cfg_lines, vec!["[A]", "key = 1", "new = 3", "", "# about B", "[B]", "key = 2"]);
+    }
+
+    #[test]
+    fn set_property_adds_section_ignoring_commented_header() {
+        let mut ops = IswConfigOps::from_text("[A]\nkey = 1\n# [B]\n");
+        ops.set_property("B".to_string(), "key".to_string(), "2".to_string()).unwrap();
+        assert_eq!(ops.m_cfg_lines, vec!["[A]", "key = 1", "# [B]", "", "[B]", "key = 2"]);
+
+        let mut empty = IswConfigOps::from_text("");
+        empty.set_property("A".to_string(), "key".to_string(), "1".to_string()).unwrap();
+        assert_eq!(empty.m_cfg_lines, vec!["[A]", "key = 1"]);
+    }
+}
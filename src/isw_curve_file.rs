@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use crate::isw_config_model::FanCurve;
+use crate::isw_error::IswError;
+
+/// Serialization used for exchanging fan curves
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CurveFormat {
+    Json,
+    Toml,
+}
+
+impl CurveFormat {
+    pub fn parse(format: &str) -> Result<CurveFormat, IswError> {
+        match format {
+            "json" => Ok(CurveFormat::Json),
+            "toml" => Ok(CurveFormat::Toml),
+            _ => Err(IswError::Usage("Unrecognized curve format <".to_string() + format + ">; expected 'json' or 'toml'")),
+        }
+    }
+
+    /// `format` if given, else guessed from the extension of `file`; JSON by default
+    pub fn select(format: Option<String>, file: Option<&str>) -> Result<CurveFormat, IswError> {
+        if let Some(format) = format {
+            return CurveFormat::parse(format.as_str());
+        }
+        match file {
+            Some(file) if file.to_lowercase().ends_with(".toml") => Ok(CurveFormat::Toml),
+            _ => Ok(CurveFormat::Json),
+        }
+    }
+}
+
+/// CPU and GPU fan curve of one board, as passed around between users
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FanCurveFile {
+    /// Config section the curves were taken from
+    pub section: String,
+    pub cpu: FanCurve,
+    pub gpu: FanCurve,
+}
+
+impl FanCurveFile {
+    pub fn to_text(&self, format: CurveFormat) -> Result<String, IswError> {
+        let text = match format {
+            CurveFormat::Json => serde_json::to_string_pretty(self).map_err(|error| error.to_string()),
+            CurveFormat::Toml => toml::to_string(self).map_err(|error| error.to_string()),
+        };
+        match text {
+            Ok(text) => Ok(text),
+            Err(error) => Err(IswError::File("Serializing fan curves failed with <".to_string() + error.as_str() + ">")),
+        }
+    }
+
+    pub fn from_text(text: &str, format: CurveFormat) -> Result<FanCurveFile, IswError> {
+        let file: Result<FanCurveFile, String> = match format {
+            CurveFormat::Json => serde_json::from_str(text).map_err(|error| error.to_string()),
+            CurveFormat::Toml => toml::from_str(text).map_err(|error| error.to_string()),
+        };
+        match file {
+            Ok(file) => {
+                file.cpu.validate()?;
+                file.gpu.validate()?;
+                Ok(file)
+            }
+            Err(error) => Err(IswError::Parse("Parsing fan curves failed with <".to_string() + error.as_str() + ">")),
+        }
+    }
+
+    pub fn load(file: &str, format: CurveFormat) -> Result<FanCurveFile, IswError> {
+        match std::fs::read_to_string(file) {
+            Ok(text) => FanCurveFile::from_text(text.as_str(), format),
+            Err(error) => Err(IswError::from_io("Reading file <".to_string() + file + "> failed with <"
+                + error.to_string().as_str() + ">", &error, IswError::File)),
+        }
+    }
+
+    pub fn save(&self, file: &str, format: CurveFormat) -> Result<(), IswError> {
+        match std::fs::write(file, self.to_text(format)?) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(IswError::from_io("Writing file <".to_string() + file + "> failed with <"
+                + error.to_string().as_str() + ">", &error, IswError::File)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curves() -> FanCurveFile {
+        FanCurveFile {
+            section: "16J9EMS1".to_string(),
+            cpu: FanCurve { temps: vec![55, 61, 67, 73, 79, 85], speeds: vec![0, 40, 48, 56, 64, 72, 80] },
+            gpu: FanCurve { temps: vec![55, 61, 67, 73, 79, 85], speeds: vec![0, 48, 56, 64, 72, 79, 86] },
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let text = curves().to_text(CurveFormat::Json).unwrap();
+        assert_eq!(FanCurveFile::from_text(text.as_str(), CurveFormat::Json), Ok(curves()));
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let text = curves().to_text(CurveFormat::Toml).unwrap();
+        assert!(text.starts_with("section = \"16J9EMS1\""), "{}", text);
+        assert_eq!(FanCurveFile::from_text(text.as_str(), CurveFormat::Toml), Ok(curves()));
+    }
+
+    #[test]
+    fn rejects_falling_curve() {
+        let mut falling = curves();
+        falling.gpu.temps[3] = 60;
+        let text = falling.to_text(CurveFormat::Json).unwrap();
+        assert_eq!(FanCurveFile::from_text(text.as_str(), CurveFormat::Json),
+                   Err(IswError::OutOfRange("Temperature <60> of fan curve point 3 is lower than the one before it <67>".to_string())));
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        assert!(matches!(FanCurveFile::from_text("{\"section\": 1}", CurveFormat::Json), Err(IswError::Parse(_))));
+        assert!(matches!(FanCurveFile::from_text("section = ", CurveFormat::Toml), Err(IswError::Parse(_))));
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(CurveFormat::select(None, Some("curve.TOML")), Ok(CurveFormat::Toml));
+        assert_eq!(CurveFormat::select(None, Some("curve.json")), Ok(CurveFormat::Json));
+        assert_eq!(CurveFormat::select(Some("toml".to_string()), Some("curve.json")), Ok(CurveFormat::Toml));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::isw_raw_access::IswRegisterWidth;
+use crate::isw_error::IswError;
+
+const HIGHLIGHT_ON: &str = "\x1b[7m";
+const HIGHLIGHT_OFF: &str = "\x1b[0m";
+
+/// Render `image` as the classic 16x16 hex table
+pub fn format_hex_table(image: &[u8]) -> String {
+    format_highlighted_hex_table(image, &[])
+}
+
+/// Render `image` as hex table, showing every register listed in `highlighted` inverted
+pub fn format_highlighted_hex_table(image: &[u8], highlighted: &[u64]) -> String {
+    let mut table = "     |".to_string();
+    for column in 0..16 {
+        table += format!(" _{:X}", column).as_str();
+    }
+    table += "\n-----+------------------------------------------------\n";
+
+    for (row, bytes) in image.chunks(16).enumerate() {
+        table += format!("  {:X}_ |", row).as_str();
+        for (column, byte) in bytes.iter().enumerate() {
+            let address = (row * 16 + column) as u64;
+            if highlighted.contains(&address) {
+                table += format!(" {}{:02x}{}", HIGHLIGHT_ON, byte, HIGHLIGHT_OFF).as_str();
+            } else {
+                table += format!(" {:02x}", byte).as_str();
+            }
+        }
+        table += "\n";
+    }
+    table
+}
+
+/// List the registers in `named` together with their current value in `image`
+pub fn format_annotations(image: &[u8], named: &[(u64, String)]) -> String {
+    let mut legend = String::new();
+    for (address, name) in named {
+        let value = match image.get(*address as usize) {
+            None => "--".to_string(),
+            Some(value) => format!("{:02x}", value),
+        };
+        legend += format!("0x{:02x} = {} ({})\n", address, value, name).as_str();
+    }
+    legend
+}
+
+/// Value of the register at `address` in `image`, decoded like `IswRawAccess::read_hw`
+pub fn read_image(image: &[u8], address: u64, width: IswRegisterWidth) -> Result<u16, IswError> {
+    let start = address as usize;
+    let bytes = match image.get(start..start + width.size()) {
+        Some(bytes) => bytes,
+        None => {
+            return Err(IswError::OutOfRange("Register <".to_string() + format!("0x{:02x}", address).as_str()
+                + "> is outside the EC image of " + image.len().to_string().as_str() + " bytes"));
+        }
+    };
+    match width {
+        IswRegisterWidth::U8 => Ok(bytes[0] as u16),
+        IswRegisterWidth::U16Le => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+        IswRegisterWidth::U16Be => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+    }
+}
+
+/// Every register that differs between `old` and `new` as (address, old value, new value)
+pub fn diff_images(old: &[u8], new: &[u8]) -> Vec<(u64, u8, u8)> {
+    old.iter().zip(new.iter()).enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(address, (old, new))| (address as u64, *old, *new))
+        .collect()
+}
+
+/// One line per changed register, labelled with its names from `named`
+pub fn format_diff(diff: &[(u64, u8, u8)], named: &[(u64, String)]) -> String {
+    let mut lines = String::new();
+    for (address, old, new) in diff {
+        lines += format!("0x{:02x}: {:02x} -> {:02x}", address, old, new).as_str();
+        let names: Vec<&str> = named.iter()
+            .filter(|(named_address, _)| named_address == address)
+            .map(|(_, name)| name.as_str())
+            .collect();
+        if !names.is_empty() {
+            lines += format!(" ({})", names.join(", ")).as_str();
+        }
+        lines += "\n";
+    }
+    lines
+}
@@ -0,0 +1,117 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::sync::Arc;
+use crate::isw_sim_backend::IswSimBackend;
+use crate::isw_error::IswError;
+
+/// Access path to the embedded controller's register space
+pub trait IswEcBackend: Send + Sync {
+    /// Fill `buf` with the registers starting at `base_address`
+    fn read(&self, base_address: u64, buf: &mut [u8]) -> Result<(), IswError>;
+    /// Write `data` to the registers starting at `base_address`
+    fn write(&self, base_address: u64, data: &[u8]) -> Result<(), IswError>;
+    /// Read the whole register space in one pass
+    fn dump(&self) -> Result<Vec<u8>, IswError> {
+        let mut buf = vec![0; IswFileBackend::EC_SIZE];
+        self.read(0, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Backend talking to a character/debugfs node exposing the EC as a flat file
+pub struct IswFileBackend {
+    m_sys_fs_file: String,
+}
+
+impl IswFileBackend {
+    pub const EC_SIZE: usize = 256;
+    pub const DEBUGFS_IO_FILE: &'static str = "/sys/kernel/debug/ec/ec0/io";
+    pub const ACPI_EC_FILE: &'static str = "/dev/ec";
+
+    pub fn new(sys_fs_file: String) -> IswFileBackend {
+        IswFileBackend {
+            m_sys_fs_file: sys_fs_file
+        }
+    }
+
+    fn opening_error(&self, error: std::io::Error) -> IswError {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            return IswError::EcNodeMissing { path: self.m_sys_fs_file.clone() };
+        }
+        IswError::from_io("Opening file <".to_string() + self.m_sys_fs_file.as_str()
+            + "> failed with <" + error.to_string().as_str() + ">", &error, IswError::Io)
+    }
+
+    fn seek_error(&self, base_address: u64, error: std::io::Error) -> IswError {
+        IswError::Io("Seeking pos <".to_string() + base_address.to_string().as_str() + "> of file <"
+            + self.m_sys_fs_file.as_str() + "> failed with <" + error.to_string().as_str() + ">")
+    }
+
+    fn rw_error(&self, read: bool, error: std::io::Error) -> IswError {
+        let action = if read { "Could not read from file <" } else { "Could not write to file <" };
+        IswError::from_io(action.to_string() + self.m_sys_fs_file.as_str() + ">: " + error.to_string().as_str(),
+                          &error, IswError::Io)
+    }
+}
+
+impl IswEcBackend for IswFileBackend {
+    fn read(&self, base_address: u64, buf: &mut [u8]) -> Result<(), IswError> {
+        match std::fs::OpenOptions::new().read(true).open(self.m_sys_fs_file.clone()) {
+            Ok(mut f) => {
+                match f.seek(SeekFrom::Start(base_address)) {
+                    Ok(_) => {
+                        match f.read_exact(buf) {
+                            Ok(_) => Ok(()),
+                            Err(error) => Err(self.rw_error(true, error))
+                        }
+                    }
+                    Err(error) => Err(self.seek_error(base_address, error))
+                }
+            }
+            Err(error) => {
+                Err(self.opening_error(error))
+            }
+        }
+    }
+
+    fn write(&self, base_address: u64, data: &[u8]) -> Result<(), IswError> {
+        match std::fs::OpenOptions::new().write(true).open(self.m_sys_fs_file.clone()) {
+            Ok(mut f) => {
+                match f.seek(SeekFrom::Start(base_address)) {
+                    Ok(_) => {
+                        match f.write_all(data) {
+                            Ok(_) => Ok(()),
+                            Err(error) => Err(self.rw_error(false, error))
+                        }
+                    }
+                    Err(error) => Err(self.seek_error(base_address, error))
+                }
+            }
+            Err(error) => {
+                Err(self.opening_error(error))
+            }
+        }
+    }
+}
+
+/// Create the backend named by `kind`; `node` overrides the backend's default path
+/// and names the image file of the simulated EC, which is stored back if `save_image` is set
+pub fn create_backend(kind: String, node: Option<String>, save_image: bool) -> Result<Arc<dyn IswEcBackend>, IswError> {
+    match kind.as_ref() {
+        "debugfs" => {
+            let path = node.unwrap_or_else(|| IswFileBackend::DEBUGFS_IO_FILE.to_string());
+            Ok(Arc::new(IswFileBackend::new(path)))
+        }
+        "acpi-ec" => {
+            let path = node.unwrap_or_else(|| IswFileBackend::ACPI_EC_FILE.to_string());
+            Ok(Arc::new(IswFileBackend::new(path)))
+        }
+        "sim" => {
+            match node {
+                None => Ok(Arc::new(IswSimBackend::new())),
+                Some(image) => Ok(Arc::new(IswSimBackend::load(image, save_image)?))
+            }
+        }
+        _ => Err(IswError::Usage("Unrecognized backend <".to_string() + kind.as_str() + ">"))
+    }
+}
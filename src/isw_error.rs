@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// Ends the message of a write breaking the rules without --force
+pub const FORCE_HINT: &str = "; use --force to write anyway";
+/// Ends the message of a write breaking the rules that was not confirmed
+pub const DECLINED_HINT: &str = "; write declined";
+
+/// Everything that can go wrong talking to the EC or handling its config
+#[derive(Clone, PartialEq, Debug)]
+pub enum IswError {
+    /// Access to the EC node or a file was refused
+    PermissionDenied(String),
+    /// The EC node does not exist, usually because ec_sys is not loaded
+    EcNodeMissing { path: String },
+    /// Seeking, reading or writing the EC failed
+    Io(String),
+    /// Reading or writing a state, backup or curve file failed
+    File(String),
+    /// A section or key is missing from the config
+    ConfigKeyMissing { section: String, key: String },
+    /// The config is inconsistent, e.g. an inheritance cycle or a missing `--config` file
+    Config(String),
+    /// A config value or file could not be parsed
+    Parse(String),
+    /// A value does not fit into its register or the range isw allows
+    OutOfRange(String),
+    /// An argument has a value isw does not know
+    Usage(String),
+    /// A write breaks the rules of the address profile; `declined` if --force was not confirmed
+    WriteRefused { reason: String, declined: bool },
+    /// The board could not be matched to a config section
+    UnsupportedBoard(String),
+}
+
+impl IswError {
+    /// `message` about a failed file operation, as `PermissionDenied` if `error` says so,
+    /// else as what `other` makes of it
+    pub fn from_io(message: String, error: &std::io::Error, other: fn(String) -> IswError) -> IswError {
+        if error.kind() == std::io::ErrorKind::PermissionDenied {
+            return IswError::PermissionDenied(message);
+        }
+        other(message)
+    }
+
+    /// What the user can do about the error, if there is something
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            IswError::PermissionDenied(_) => {
+                Some("run as root; the debugfs backend needs 'modprobe ec_sys write_support=1'")
+            }
+            IswError::EcNodeMissing { .. } => {
+                Some("load ec_sys with 'modprobe ec_sys write_support=1', or pick another node with --backend or --ec-node")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for IswError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IswError::PermissionDenied(message) => write!(f, "{}", message),
+            IswError::EcNodeMissing { path } => write!(f, "EC node <{}> does not exist", path),
+            IswError::Io(message) => write!(f, "{}", message),
+            IswError::File(message) => write!(f, "{}", message),
+            IswError::ConfigKeyMissing { section, key } => write!(f, "Could not get <{}> of <{}>", key, section),
+            IswError::Config(message) => write!(f, "{}", message),
+            IswError::Parse(message) => write!(f, "{}", message),
+            IswError::OutOfRange(message) => write!(f, "{}", message),
+            IswError::Usage(message) => write!(f, "{}", message),
+            IswError::WriteRefused { reason, declined: false } => write!(f, "{}{}", reason, FORCE_HINT),
+            IswError::WriteRefused { reason, declined: true } => write!(f, "{}{}", reason, DECLINED_HINT),
+            IswError::UnsupportedBoard(message) => write!(f, "{}", message),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::isw_error::IswError;
+
+/// Any failure not covered below
+pub const EXIT_FAILURE: i32 = 1;
+/// Invalid command line, including writes refused by the allowlist; same code as clap uses
+pub const EXIT_USAGE: i32 = 2;
+/// Access to the EC or a file was denied, or the EC node does not exist
+pub const EXIT_PERMISSION: i32 = 3;
+/// The board could not be matched to a config section
+pub const EXIT_UNSUPPORTED_BOARD: i32 = 4;
+/// Reading or writing the EC failed
+pub const EXIT_EC_IO: i32 = 5;
+/// The config is missing, broken or lacks the requested section, or a data file or EC string could not be parsed
+pub const EXIT_CONFIG: i32 = 6;
+
+pub const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0    success
+    1    other failure
+    2    usage error, value out of range, or write refused by the allowlist
+    3    permission denied, or EC node missing
+    4    unsupported board
+    5    EC read or write failed
+    6    config error, or unparsable curve, backup or journal file or firmware string";
+
+/// Exit code documented for `error`
+pub fn exit_code(error: &IswError) -> i32 {
+    match error {
+        IswError::PermissionDenied(_) | IswError::EcNodeMissing { .. } => EXIT_PERMISSION,
+        IswError::Io(_) => EXIT_EC_IO,
+        IswError::File(_) => EXIT_FAILURE,
+        IswError::ConfigKeyMissing { .. } | IswError::Config(_) | IswError::Parse(_) => EXIT_CONFIG,
+        IswError::OutOfRange(_) | IswError::Usage(_) | IswError::WriteRefused { .. } => EXIT_USAGE,
+        IswError::UnsupportedBoard(_) => EXIT_UNSUPPORTED_BOARD,
+    }
+}
+
+/// Print `error` and what to do about it to stderr, and exit with its code
+pub fn fail(error: IswError) -> ! {
+    eprintln!("isw-rs: {}", error);
+    if let Some(hint) = error.hint() {
+        eprintln!("isw-rs: {}", hint);
+    }
+    std::process::exit(exit_code(&error))
+}
@@ -0,0 +1,249 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use crate::isw_rs_base::{IswRsBase, IswSensor, IswSnapshot, IswValue};
+use crate::isw_error::IswError;
+
+const METRICS_PATH: &str = "/metrics";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// Longest a client may take to send its request or accept the response; scrapes are served one by one
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest request line accepted, in bytes
+const MAX_REQUEST_LINE: u64 = 8 * 1024;
+/// Longest header block accepted, in bytes
+const MAX_HEADERS: u64 = 16 * 1024;
+
+/// A gauge of the exporter and the sensors feeding its samples, labelled by fan if given
+struct IswMetric {
+    name: &'static str,
+    help: &'static str,
+    samples: &'static [(Option<&'static str>, IswSensor)],
+}
+
+const METRICS: [IswMetric; 6] = [
+    IswMetric {
+        name: "isw_cpu_temperature_celsius",
+        help: "Temperature of the CPU",
+        samples: &[(None, IswSensor::CpuTemp)],
+    },
+    IswMetric {
+        name: "isw_gpu_temperature_celsius",
+        help: "Temperature of the GPU",
+        samples: &[(None, IswSensor::GpuTemp)],
+    },
+    IswMetric {
+        name: "isw_fan_rpm",
+        help: "Fan speed in rotations per minute",
+        samples: &[(Some("cpu"), IswSensor::CpuFanRpm), (Some("gpu"), IswSensor::GpuFanRpm)],
+    },
+    IswMetric {
+        name: "isw_fan_speed_percent",
+        help: "Fan speed in percent of its maximum",
+        samples: &[(Some("cpu"), IswSensor::CpuFanSpeed), (Some("gpu"), IswSensor::GpuFanSpeed)],
+    },
+    IswMetric {
+        name: "isw_cooler_boost",
+        help: "1 if Coolerboost is on, else 0",
+        samples: &[(None, IswSensor::CoolerBoost)],
+    },
+    IswMetric {
+        name: "isw_battery_charge_threshold",
+        help: "Battery charge level in percent at which charging stops",
+        samples: &[(None, IswSensor::BatteryThreshold)],
+    },
+];
+
+/// `snapshot` in the Prometheus text exposition format
+pub fn format_metrics(snapshot: &IswSnapshot) -> String {
+    let mut text = String::new();
+    for metric in METRICS.iter() {
+        text += format!("# HELP {} {}\n# TYPE {} gauge\n", metric.name, metric.help, metric.name).as_str();
+        for (fan, sensor) in metric.samples {
+            let value = match snapshot.reading(*sensor).map(|reading| &reading.value) {
+                Some(IswValue::Number(number)) => number.to_string(),
+                Some(IswValue::Flag(flag)) => (*flag as u8).to_string(),
+                _ => continue,
+            };
+            match fan {
+                Some(fan) => text += format!("{}{{fan=\"{}\"}} {}\n", metric.name, fan, value).as_str(),
+                None => text += format!("{} {}\n", metric.name, value).as_str(),
+            }
+        }
+    }
+    text
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    // one write, so the whole answer is out before the connection is closed on a client still sending
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, content_type, body.len(), body);
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+/// Read a line of at most `limit` bytes into `line`; false if the line is longer
+fn read_line_capped(reader: &mut impl BufRead, line: &mut String, limit: u64) -> std::io::Result<bool> {
+    let read = reader.by_ref().take(limit).read_line(line)?;
+    Ok((read as u64) < limit || line.ends_with('\n'))
+}
+
+/// The request line of a request whose headers were read up to the empty line, or the status
+/// and body to refuse it with
+fn read_request(reader: &mut impl BufRead) -> std::io::Result<Result<String, (&'static str, &'static str)>> {
+    let mut request = String::new();
+    if !read_line_capped(reader, &mut request, MAX_REQUEST_LINE)? {
+        return Ok(Err(("414 URI Too Long", "Request line too long\n")));
+    }
+    // the headers are of no interest, but must be read before answering
+    let mut header = String::new();
+    let mut budget = MAX_HEADERS;
+    loop {
+        header.clear();
+        if !read_line_capped(reader, &mut header, budget)? {
+            return Ok(Err(("431 Request Header Fields Too Large", "Headers too long\n")));
+        }
+        budget -= header.len() as u64;
+        if header.trim().is_empty() {
+            return Ok(Ok(request));
+        }
+    }
+}
+
+fn handle(stream: &mut TcpStream, isw: &IswRsBase) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = match read_request(&mut reader)? {
+        Ok(request) => request,
+        Err((status, body)) => return respond(stream, status, "text/plain", body),
+    };
+
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    if method != "GET" {
+        return respond(stream, "405 Method Not Allowed", "text/plain", "Only GET is supported\n");
+    }
+    if path.split('?').next() != Some(METRICS_PATH) {
+        return respond(stream, "404 Not Found", "text/plain", "Metrics are served at /metrics\n");
+    }
+    match isw.snapshot() {
+        Ok(snapshot) => respond(stream, "200 OK", METRICS_CONTENT_TYPE, format_metrics(&snapshot).as_str()),
+        Err(error) => {
+            eprintln!("isw-rs: {}", error);
+            respond(stream, "500 Internal Server Error", "text/plain", (error.to_string() + "\n").as_str())
+        }
+    }
+}
+
+/// Serve the EC values at /metrics on `address` until the process is stopped
+pub fn serve(address: &str, isw: &IswRsBase) -> Result<(), IswError> {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            return Err(IswError::from_io("Listening on <".to_string() + address + "> failed with <"
+                + error.to_string().as_str() + ">", &error, IswError::Usage));
+        }
+    };
+    eprintln!("Serving metrics at http://{}{}", address, METRICS_PATH);
+    for stream in listener.incoming() {
+        let result = stream.and_then(|mut stream| handle(&mut stream, isw));
+        if let Err(error) = result {
+            eprintln!("isw-rs: Answering scrape failed with <{}>", error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::isw_rs_base::IswReading;
+
+    fn snapshot() -> IswSnapshot {
+        let values = [
+            (IswSensor::CpuTemp, IswValue::Number(61)),
+            (IswSensor::CpuFanSpeed, IswValue::Number(45)),
+            (IswSensor::CpuFanRpm, IswValue::Number(2650)),
+            (IswSensor::GpuTemp, IswValue::Number(48)),
+            (IswSensor::GpuFanSpeed, IswValue::Number(0)),
+            (IswSensor::GpuFanRpm, IswValue::Number(0)),
+            (IswSensor::CoolerBoost, IswValue::Flag(true)),
+            (IswSensor::UsbBacklight, IswValue::Text("off".to_string())),
+            (IswSensor::BatteryThreshold, IswValue::Number(80)),
+            (IswSensor::FanMode, IswValue::Text("auto".to_string())),
+        ];
+        let readings = values.iter()
+            .map(|(sensor, value)| IswReading { name: sensor.name().to_string(), value: value.clone(), unit: sensor.unit().to_string(), raw: 0 })
+            .collect();
+        IswSnapshot { readings }
+    }
+
+    #[test]
+    fn format_metrics_writes_exposition_text() {
+        assert_eq!(format_metrics(&snapshot()), "\
+# HELP isw_cpu_temperature_celsius Temperature of the CPU
+# TYPE isw_cpu_temperature_celsius gauge
+isw_cpu_temperature_celsius 61
+# HELP isw_gpu_temperature_celsius Temperature of the GPU
+# TYPE isw_gpu_temperature_celsius gauge
+isw_gpu_temperature_celsius 48
+# HELP isw_fan_rpm Fan speed in rotations per minute
+# TYPE isw_fan_rpm gauge
+isw_fan_rpm{fan=\"cpu\"} 2650
+isw_fan_rpm{fan=\"gpu\"} 0
+# HELP isw_fan_speed_percent Fan speed in percent of its maximum
+# TYPE isw_fan_speed_percent gauge
+isw_fan_speed_percent{fan=\"cpu\"} 45
+isw_fan_speed_percent{fan=\"gpu\"} 0
+# HELP isw_cooler_boost 1 if Coolerboost is on, else 0
+# TYPE isw_cooler_boost gauge
+isw_cooler_boost 1
+# HELP isw_battery_charge_threshold Battery charge level in percent at which charging stops
+# TYPE isw_battery_charge_threshold gauge
+isw_battery_charge_threshold 80
+");
+    }
+
+    #[test]
+    fn format_metrics_skips_missing_readings() {
+        let mut snapshot = snapshot();
+        snapshot.readings.retain(|reading| reading.name != IswSensor::GpuFanRpm.name());
+        let text = format_metrics(&snapshot);
+        assert!(text.contains("isw_fan_rpm{fan=\"cpu\"} 2650\n# HELP isw_fan_speed_percent"), "{}", text);
+    }
+
+    #[test]
+    fn read_line_capped_stops_at_limit() {
+        let mut line = String::new();
+        assert!(read_line_capped(&mut Cursor::new("GET / HTTP/1.1\r\n"), &mut line, 16).unwrap());
+        assert_eq!(line, "GET / HTTP/1.1\r\n");
+        line.clear();
+        assert!(!read_line_capped(&mut Cursor::new("GET / HTTP/1.1\r\n"), &mut line, 15).unwrap());
+        line.clear();
+        assert!(read_line_capped(&mut Cursor::new("no newline"), &mut line, 16).unwrap());
+    }
+
+    #[test]
+    fn read_request_accepts_normal_request() {
+        let mut reader = Cursor::new("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(read_request(&mut reader).unwrap(), Ok("GET /metrics HTTP/1.1\r\n".to_string()));
+    }
+
+    #[test]
+    fn read_request_refuses_long_request_line() {
+        let request = "GET /".to_string() + "a".repeat(MAX_REQUEST_LINE as usize).as_str() + " HTTP/1.1\r\n\r\n";
+        assert_eq!(read_request(&mut Cursor::new(request)).unwrap(), Err(("414 URI Too Long", "Request line too long\n")));
+    }
+
+    #[test]
+    fn read_request_refuses_long_headers() {
+        // each header fits on its own, together they exceed the budget
+        let header = "X-Filler: ".to_string() + "a".repeat(1000).as_str() + "\r\n";
+        let request = "GET /metrics HTTP/1.1\r\n".to_string() + header.repeat(20).as_str() + "\r\n";
+        assert_eq!(read_request(&mut Cursor::new(request)).unwrap(),
+                   Err(("431 Request Header Fields Too Large", "Headers too long\n")));
+        let request = "GET /metrics HTTP/1.1\r\n".to_string() + header.repeat(10).as_str() + "\r\n";
+        assert!(read_request(&mut Cursor::new(request)).unwrap().is_ok());
+    }
+}
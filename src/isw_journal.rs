@@ -0,0 +1,111 @@
+use std::io::Write;
+use serde::{Deserialize, Serialize};
+use crate::isw_raw_access::IswRegisterWidth;
+use crate::isw_state;
+use crate::isw_error::IswError;
+
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// One register write, enough to take it back
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IswJournalEntry {
+    pub timestamp: String,
+    pub command: String,
+    pub address: u64,
+    pub width: IswRegisterWidth,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// Append-only log of EC writes, one JSON object per line
+#[derive(Clone)]
+pub struct IswJournal {
+    m_file: String,
+    m_command: String,
+}
+
+impl IswJournal {
+    /// Journal in $XDG_STATE_HOME/isw-rs, tagging each entry with `command`
+    pub fn open(command: String) -> Result<IswJournal, IswError> {
+        let dir = isw_state::state_dir()?;
+        Ok(IswJournal {
+            m_file: dir.join(JOURNAL_FILE).to_string_lossy().to_string(),
+            m_command: command,
+        })
+    }
+
+    /// Journal in `file` instead of the state directory
+    #[cfg(test)]
+    pub fn at(file: String, command: String) -> IswJournal {
+        IswJournal {
+            m_file: file,
+            m_command: command,
+        }
+    }
+
+    fn io_error(&self, action: &str, error: std::io::Error) -> IswError {
+        IswError::from_io(action.to_string() + " journal <" + self.m_file.as_str() + "> failed with <"
+            + error.to_string().as_str() + ">", &error, IswError::File)
+    }
+
+    pub fn record(&self, address: u64, width: IswRegisterWidth, old: u16, new: u16) -> Result<(), IswError> {
+        let entry = IswJournalEntry {
+            timestamp: isw_state::timestamp(),
+            command: self.m_command.clone(),
+            address,
+            width,
+            old,
+            new,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(error) => return Err(IswError::File("Serializing journal entry failed with <".to_string() + error.to_string().as_str() + ">")),
+        };
+
+        if let Some(dir) = std::path::Path::new(self.m_file.as_str()).parent() {
+            if let Err(error) = std::fs::create_dir_all(dir) {
+                return Err(self.io_error("Creating directory of", error));
+            }
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(self.m_file.as_str());
+        match file.and_then(|mut file| writeln!(file, "{}", line)) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.io_error("Appending to", error)),
+        }
+    }
+
+    /// All recorded writes, oldest first
+    pub fn entries(&self) -> Result<Vec<IswJournalEntry>, IswError> {
+        let text = match std::fs::read_to_string(self.m_file.as_str()) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(self.io_error("Reading", error)),
+        };
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(error) => {
+                    return Err(IswError::Parse("Parsing line ".to_string() + (number + 1).to_string().as_str() + " of journal <"
+                        + self.m_file.as_str() + "> failed with <" + error.to_string().as_str() + ">"));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Rewrite the journal with `entries` only
+    pub fn replace(&self, entries: &[IswJournalEntry]) -> Result<(), IswError> {
+        let mut text = String::new();
+        for entry in entries {
+            match serde_json::to_string(entry) {
+                Ok(line) => text += (line + "\n").as_str(),
+                Err(error) => return Err(IswError::File("Serializing journal entry failed with <".to_string() + error.to_string().as_str() + ">")),
+            }
+        }
+        match std::fs::write(self.m_file.as_str(), text) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.io_error("Writing", error)),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::isw_rs_base::IswReading;
+use crate::isw_error::IswError;
+
+/// Presentation of values read from the EC
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    Plain,
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(format: &str) -> Result<OutputFormat, IswError> {
+        match format {
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(IswError::Usage("Unrecognized output format <".to_string() + format
+                + ">; expected 'plain', 'json', 'yaml' or 'csv'")),
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        return "\"".to_string() + field.replace('"', "\"\"").as_str() + "\"";
+    }
+    field.to_string()
+}
+
+/// `readings` as a JSON array, a YAML sequence, CSV with a header line or plain `name: value` lines
+pub fn format_readings(readings: &[IswReading], format: OutputFormat) -> Result<String, IswError> {
+    let text = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(readings).map(|text| text + "\n").map_err(|error| error.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(readings).map_err(|error| error.to_string()),
+        OutputFormat::Csv => {
+            let mut text = "name,value,unit,raw\n".to_string();
+            for reading in readings {
+                text += (csv_field(reading.name.as_str()) + "," + csv_field(reading.value.to_string().as_str()).as_str()
+                    + "," + csv_field(reading.unit.as_str()).as_str() + "," + reading.raw.to_string().as_str()
+                    + "\n").as_str();
+            }
+            Ok(text)
+        }
+        OutputFormat::Plain => Ok(readings.iter()
+            .map(|reading| reading.name.clone() + ": " + reading.value.to_string().as_str() + "\n")
+            .collect()),
+    };
+    match text {
+        Ok(text) => Ok(text),
+        Err(error) => Err(IswError::File("Serializing readings failed with <".to_string() + error.as_str() + ">")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isw_rs_base::IswValue;
+
+    fn reading(name: &str, value: IswValue, unit: &str, raw: u16) -> IswReading {
+        IswReading { name: name.to_string(), value, unit: unit.to_string(), raw }
+    }
+
+    #[test]
+    fn csv_keeps_field_order() {
+        let readings = vec![
+            reading("cpu_temp", IswValue::Number(54), "celsius", 54),
+            reading("cooler_boost", IswValue::Flag(true), "", 0x80),
+        ];
+        assert_eq!(format_readings(&readings, OutputFormat::Csv),
+                   Ok("name,value,unit,raw\ncpu_temp,54,celsius,54\ncooler_boost,true,,128\n".to_string()));
+    }
+
+    #[test]
+    fn csv_quotes_separators_quotes_and_newlines() {
+        let readings = vec![reading("fan_mode", IswValue::Text("say \"hi\", then\nleave".to_string()), "a,b", 12)];
+        assert_eq!(format_readings(&readings, OutputFormat::Csv),
+                   Ok("name,value,unit,raw\nfan_mode,\"say \"\"hi\"\", then\nleave\",\"a,b\",12\n".to_string()));
+    }
+
+    #[test]
+    fn plain_prints_name_and_value() {
+        let readings = vec![reading("cpu_temp", IswValue::Number(54), "celsius", 54)];
+        assert_eq!(format_readings(&readings, OutputFormat::Plain), Ok("cpu_temp: 54\n".to_string()));
+    }
+}
@@ -1,76 +1,510 @@
-use std::io::prelude::*;
-use std::io::SeekFrom;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::isw_ec_backend::IswEcBackend;
+use crate::isw_journal::{IswJournal, IswJournalEntry};
+use crate::isw_error::IswError;
+
+/// Size and byte order of a single EC register
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum IswRegisterWidth {
+    U8,
+    U16Le,
+    U16Be,
+}
+
+impl IswRegisterWidth {
+    pub fn parse(width: &str) -> Result<IswRegisterWidth, IswError> {
+        match width.to_lowercase().as_ref() {
+            "u8" => Ok(IswRegisterWidth::U8),
+            "u16le" => Ok(IswRegisterWidth::U16Le),
+            "u16be" => Ok(IswRegisterWidth::U16Be),
+            _ => Err(IswError::Usage("Unrecognized register width <".to_string() + width
+                + ">; expected 'u8', 'u16le' or 'u16be'"))
+        }
+    }
+
+    /// Number of bytes the register occupies
+    pub fn size(&self) -> usize {
+        match self {
+            IswRegisterWidth::U8 => 1,
+            IswRegisterWidth::U16Le | IswRegisterWidth::U16Be => 2,
+        }
+    }
+}
+
+/// A register that may be written without --force, and the values it accepts
+#[derive(Clone, PartialEq, Debug)]
+pub struct IswWriteRule {
+    pub name: String,
+    pub address: u64,
+    pub width: IswRegisterWidth,
+    pub allowed: Vec<RangeInclusive<u16>>,
+}
+
+/// Asked with the reason a write breaks the rules; the write goes ahead if it returns true
+pub type IswForceConfirmation = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+
+/// A write held back by dry-run mode
+#[derive(Clone, PartialEq, Debug)]
+pub struct IswPendingWrite {
+    pub address: u64,
+    pub width: IswRegisterWidth,
+    pub old: u16,
+    pub new: u16,
+    /// Name of the register in the address profile, if it is declared there
+    pub name: Option<String>,
+}
+
+/// Told about every write instead of it reaching the EC
+pub type IswDryRunReporter = Arc<dyn Fn(&IswPendingWrite) + Send + Sync>;
 
 #[derive(Clone)]
 pub struct IswRawAccess {
-    m_sys_fs_file: String,
+    m_backend: Arc<dyn IswEcBackend>,
+    m_journal: Option<IswJournal>,
+    m_write_rules: Option<Vec<IswWriteRule>>,
+    m_force: Option<IswForceConfirmation>,
+    m_dry_run: Option<IswDryRunReporter>,
 }
 
 impl IswRawAccess {
-    pub fn new(sys_fs_file: String) -> IswRawAccess {
-        let s = IswRawAccess {
-            m_sys_fs_file: sys_fs_file
+    pub fn new(backend: Arc<dyn IswEcBackend>) -> IswRawAccess {
+        IswRawAccess {
+            m_backend: backend,
+            m_journal: None,
+            m_write_rules: None,
+            m_force: None,
+            m_dry_run: None,
+        }
+    }
+
+    /// Hand every following write to `reporter` instead of the EC; `None` writes for real
+    pub fn set_dry_run(&mut self, reporter: Option<IswDryRunReporter>) {
+        self.m_dry_run = reporter;
+    }
+
+    /// Restrict `write_hw` to the registers and values of `rules`; `None` allows every write
+    pub fn set_write_rules(&mut self, rules: Option<Vec<IswWriteRule>>) {
+        self.m_write_rules = rules;
+    }
+
+    /// Let writes breaking the rules through once `confirmation` agrees; `None` refuses them
+    pub fn set_force(&mut self, confirmation: Option<IswForceConfirmation>) {
+        self.m_force = confirmation;
+    }
+
+    /// The rule for the register at `base_address`, or why a `width` write there breaks the rules
+    fn find_rule(&self, base_address: u64, width: IswRegisterWidth) -> Result<Option<&IswWriteRule>, String> {
+        let rules = match self.m_write_rules.as_ref() {
+            Some(rules) => rules,
+            None => return Ok(None),
+        };
+        let target = format!("0x{:02x}", base_address);
+        let rule = match rules.iter().find(|rule| rule.address == base_address) {
+            Some(rule) => rule,
+            None => return Err("Address <".to_string() + target.as_str() + "> is not declared in the address profile"),
+        };
+        if rule.width != width {
+            return Err("Register <".to_string() + rule.name.as_str() + "> at <" + target.as_str()
+                + "> is declared as " + format!("{:?}", rule.width).as_str() + ", not " + format!("{:?}", width).as_str());
+        }
+        Ok(Some(rule))
+    }
+
+    /// Why writing the register at `base_address` as `width` breaks the rules, whatever the value, if it does
+    pub fn check_register(&self, base_address: u64, width: IswRegisterWidth) -> Option<String> {
+        self.find_rule(base_address, width).err()
+    }
+
+    /// Why writing `value` to the register at `base_address` breaks the rules, if it does
+    pub fn check_write(&self, base_address: u64, width: IswRegisterWidth, value: u16) -> Option<String> {
+        let rule = match self.find_rule(base_address, width) {
+            Ok(Some(rule)) => rule,
+            Ok(None) => return None,
+            Err(reason) => return Some(reason),
         };
-        return s;
+        let target = format!("0x{:02x}", base_address);
+        if !rule.allowed.iter().any(|range| range.contains(&value)) {
+            let allowed: Vec<String> = rule.allowed.iter()
+                .map(|range| if range.start() == range.end() {
+                    range.start().to_string()
+                } else {
+                    range.start().to_string() + ".." + range.end().to_string().as_str()
+                })
+                .collect();
+            return Some("Value <".to_string() + value.to_string().as_str() + "> is not allowed for <"
+                + rule.name.as_str() + "> at <" + target.as_str() + ">; allowed: " + allowed.join(", ").as_str());
+        }
+        None
+    }
+
+    /// Record every following `write_hw` in `journal`, so it can be undone
+    pub fn set_journal(&mut self, journal: Option<IswJournal>) {
+        self.m_journal = journal;
+    }
+
+    pub fn write_u8(&self, base_address: u64, value: u8) -> Result<(), IswError> {
+        self.m_backend.write(base_address, &[value])
     }
 
-    fn format_opening_error(&self, error: String) -> String {
-        return "Opening file <".to_string() + self.m_sys_fs_file.clone().as_str()
-            + "> failed with <" + error.as_str() + ">";
+    pub fn write_u16_le(&self, base_address: u64, value: u16) -> Result<(), IswError> {
+        self.m_backend.write(base_address, &value.to_le_bytes())
     }
 
-    fn format_seek_error(&self, base_address: u64, error: String) -> String {
-        return "Seeking pos <".to_string() + base_address.to_string().as_str() + "> of file <"
-            + self.m_sys_fs_file.clone().as_str() + "> failed with <" + error.as_str() + ">";
+    pub fn write_u16_be(&self, base_address: u64, value: u16) -> Result<(), IswError> {
+        self.m_backend.write(base_address, &value.to_be_bytes())
     }
 
-    fn format_rw_error(&self, read: bool) -> String {
-        if read {
-            return "Could not read from file <".to_string() + self.m_sys_fs_file.clone().as_str()
-                + ">";
+    pub fn read_u8(&self, base_address: u64) -> Result<u8, IswError> {
+        let mut buf = [0];
+        self.m_backend.read(base_address, &mut buf)?;
+        Ok(buf[0])
+    }
+
+    pub fn read_u16_le(&self, base_address: u64) -> Result<u16, IswError> {
+        let mut buf = [0, 0];
+        self.m_backend.read(base_address, &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    pub fn read_u16_be(&self, base_address: u64) -> Result<u16, IswError> {
+        let mut buf = [0, 0];
+        self.m_backend.read(base_address, &mut buf)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    /// Write `value` to the register at `base_address`, touching only `width.size()` bytes;
+    /// writes breaking the rules need --force, the old value is journaled once the write succeeded
+    /// if a journal is set, in dry-run mode the write is only reported
+    pub fn write_hw(&self, base_address: u64, width: IswRegisterWidth, value: u16) -> Result<(), IswError> {
+        IswRawAccess::check_value(base_address, width, value)?;
+        if let Some(reason) = self.check_write(base_address, width, value) {
+            self.confirm_forced(reason)?;
         }
-        return "Could not write from file <".to_string() + self.m_sys_fs_file.clone().as_str()
-            + ">";
+        self.write_hw_trusted(base_address, width, value)
     }
 
-    pub fn write_hw(&self, base_address: u64, value: u16) -> Result<(), String> {
-        match std::fs::OpenOptions::new().write(true).open(self.m_sys_fs_file.clone()) {
-            Ok(mut f) => {
-                match f.seek(SeekFrom::Start(base_address)) {
-                    Ok(_) => {
-                        match f.write(&value.to_le_bytes()) {
-                            Ok(_) => Ok(()),
-                            Err(_) => Err(self.format_rw_error(false))
-                        }
-                    }
-                    Err(error) => Err(self.format_seek_error(base_address.clone(),
-                                                             error.to_string()))
+    /// Let a write breaking the rules for `reason` through if --force is given and confirmed
+    pub fn confirm_forced(&self, reason: String) -> Result<(), IswError> {
+        match &self.m_force {
+            None => Err(IswError::WriteRefused { reason, declined: false }),
+            Some(confirm) => {
+                if !confirm(reason.as_str()) {
+                    return Err(IswError::WriteRefused { reason, declined: true });
                 }
+                Ok(())
             }
-            Err(error) => {
-                Err(self.format_opening_error(error.to_string()))
-            }
         }
     }
 
-    pub fn read_hw(&self, base_address: u64) -> Result<u16, String> {
-        match std::fs::OpenOptions::new().read(true).open(self.m_sys_fs_file.clone()) {
-            Ok(mut f) => {
-                match f.seek(SeekFrom::Start(base_address)) {
-                    Ok(_) => {
-                        let mut buf = [0, 0];
-                        match f.read(&mut buf) {
-                            Ok(_) => Ok(u16::from_le_bytes(buf)),
-                            Err(_) => Err(self.format_rw_error(true))
-                        }
-                    }
-                    Err(error) => Err(self.format_seek_error(base_address.clone(),
-                                                             error.to_string()))
+    /// Like `write_hw`, but without checking the rules; the caller checked them already,
+    /// e.g. when restoring a backup
+    pub fn write_hw_trusted(&self, base_address: u64, width: IswRegisterWidth, value: u16) -> Result<(), IswError> {
+        let old = match (&self.m_journal, &self.m_dry_run) {
+            (Some(_), None) => Some(self.read_hw(base_address, width)?),
+            _ => None,
+        };
+        self.write_hw_unjournaled(base_address, width, value)?;
+        // only writes that reached the EC are journaled, so undo never replays a failed one
+        if let (Some(journal), Some(old)) = (&self.m_journal, old) {
+            journal.record(base_address, width, old, value)?;
+        }
+        Ok(())
+    }
+
+    /// Error if `value` does not fit into a register of `width`
+    pub fn check_value(base_address: u64, width: IswRegisterWidth, value: u16) -> Result<(), IswError> {
+        if width == IswRegisterWidth::U8 && value > u8::MAX as u16 {
+            return Err(IswError::OutOfRange("Value <".to_string() + value.to_string().as_str()
+                + "> does not fit into the 8-bit register at <"
+                + base_address.to_string().as_str() + ">"));
+        }
+        Ok(())
+    }
+
+    fn write_hw_unjournaled(&self, base_address: u64, width: IswRegisterWidth, value: u16) -> Result<(), IswError> {
+        IswRawAccess::check_value(base_address, width, value)?;
+        if let Some(reporter) = &self.m_dry_run {
+            let name = self.m_write_rules.as_ref()
+                .and_then(|rules| rules.iter().find(|rule| rule.address == base_address))
+                .map(|rule| rule.name.clone());
+            reporter(&IswPendingWrite {
+                address: base_address,
+                width,
+                old: self.read_hw(base_address, width)?,
+                new: value,
+                name,
+            });
+            return Ok(());
+        }
+        match width {
+            IswRegisterWidth::U8 => self.write_u8(base_address, value as u8),
+            IswRegisterWidth::U16Le => self.write_u16_le(base_address, value),
+            IswRegisterWidth::U16Be => self.write_u16_be(base_address, value),
+        }
+    }
+
+    /// Read the register at `base_address`, touching only `width.size()` bytes
+    pub fn read_hw(&self, base_address: u64, width: IswRegisterWidth) -> Result<u16, IswError> {
+        match width {
+            IswRegisterWidth::U8 => Ok(self.read_u8(base_address)? as u16),
+            IswRegisterWidth::U16Le => self.read_u16_le(base_address),
+            IswRegisterWidth::U16Be => self.read_u16_be(base_address),
+        }
+    }
+
+    /// Read `len` consecutive registers starting at `base_address`
+    pub fn read_block(&self, base_address: u64, len: usize) -> Result<Vec<u8>, IswError> {
+        let mut buf = vec![0; len];
+        self.m_backend.read(base_address, &mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn dump_hw(&self) -> Result<Vec<u8>, IswError> {
+        self.m_backend.dump()
+    }
+
+    /// Put back the old values of the last `steps` journaled writes, newest first, and drop
+    /// them from the journal; returns the undone writes
+    pub fn undo(&self, steps: usize) -> Result<Vec<IswJournalEntry>, IswError> {
+        let journal = match &self.m_journal {
+            Some(journal) => journal,
+            None => return Err(IswError::File("No journal of EC writes available".to_string())),
+        };
+        let mut entries = journal.entries()?;
+        if entries.is_empty() {
+            return Err(IswError::Usage("Nothing to undo".to_string()));
+        }
+
+        let mut undone = Vec::new();
+        while undone.len() < steps {
+            let entry = match entries.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Err(error) = self.write_hw_unjournaled(entry.address, entry.width, entry.old) {
+                entries.push(entry);
+                if self.m_dry_run.is_none() {
+                    journal.replace(&entries)?;
                 }
+                return Err(error);
             }
-            Err(error) => {
-                Err(self.format_opening_error(error.to_string()))
-            }
+            undone.push(entry);
+        }
+        if self.m_dry_run.is_none() {
+            journal.replace(&entries)?;
+        }
+        Ok(undone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isw_sim_backend::IswSimBackend;
+
+    fn simulated_access() -> (Arc<IswSimBackend>, IswRawAccess) {
+        let backend = Arc::new(IswSimBackend::new());
+        let access = IswRawAccess::new(backend.clone());
+        (backend, access)
+    }
+
+    /// `access` journaling to a fresh file named after `test`
+    fn journaled_access(test: &str, access: &mut IswRawAccess) -> IswJournal {
+        let file = std::env::temp_dir().join(format!("isw-rs-journal-{}-{}.jsonl", test, std::process::id()));
+        let _ = std::fs::remove_file(&file);
+        let journal = IswJournal::at(file.to_string_lossy().to_string(), "test".to_string());
+        access.set_journal(Some(journal.clone()));
+        journal
+    }
+
+    fn entry(address: u64, width: IswRegisterWidth, old: u16, new: u16) -> IswJournalEntry {
+        IswJournalEntry { timestamp: "20260101T000000Z".to_string(), command: "test".to_string(), address, width, old, new }
+    }
+
+    /// Reads like the simulated EC, refuses every write
+    struct ReadOnlyBackend(IswSimBackend);
+
+    impl IswEcBackend for ReadOnlyBackend {
+        fn read(&self, base_address: u64, buf: &mut [u8]) -> Result<(), IswError> {
+            self.0.read(base_address, buf)
         }
+
+        fn write(&self, _base_address: u64, _data: &[u8]) -> Result<(), IswError> {
+            Err(IswError::Io("Writing to the EC failed".to_string()))
+        }
+    }
+
+    #[test]
+    fn u8_write_leaves_next_register_untouched() {
+        let (backend, access) = simulated_access();
+        backend.write(0x98, &[0xaa, 0xbb]).unwrap();
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x12).unwrap();
+        assert_eq!(access.read_block(0x98, 2), Ok(vec![0x12, 0xbb]));
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0x12));
+    }
+
+    #[test]
+    fn u16_byte_orders_decode_differently() {
+        let (backend, access) = simulated_access();
+        backend.write(0x98, &[0x12, 0x34]).unwrap();
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U16Be), Ok(0x1234));
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U16Le), Ok(0x3412));
+    }
+
+    #[test]
+    fn u16_byte_orders_encode_differently() {
+        let (_, access) = simulated_access();
+        access.write_hw(0x98, IswRegisterWidth::U16Be, 0x1234).unwrap();
+        access.write_hw(0x9a, IswRegisterWidth::U16Le, 0x1234).unwrap();
+        assert_eq!(access.read_block(0x98, 4), Ok(vec![0x12, 0x34, 0x34, 0x12]));
+    }
+
+    #[test]
+    fn u8_write_rejects_wide_value() {
+        let (_, access) = simulated_access();
+        assert!(matches!(access.write_hw(0x98, IswRegisterWidth::U8, 0x100), Err(IswError::OutOfRange(_))));
+        assert_eq!(access.read_block(0x98, 2), Ok(vec![0, 0]));
+    }
+
+    #[test]
+    fn width_parses_case_insensitively() {
+        assert_eq!(IswRegisterWidth::parse("U16BE"), Ok(IswRegisterWidth::U16Be));
+        assert_eq!(IswRegisterWidth::parse("u16le"), Ok(IswRegisterWidth::U16Le));
+        assert!(matches!(IswRegisterWidth::parse("u32"), Err(IswError::Usage(_))));
+    }
+
+    #[test]
+    fn undo_restores_old_values_newest_first() {
+        let (_, mut access) = simulated_access();
+        let journal = journaled_access("newest-first", &mut access);
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x80).unwrap();
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x81).unwrap();
+        access.write_hw(0xef, IswRegisterWidth::U8, 0x40).unwrap();
+        assert_eq!(journal.entries().unwrap().len(), 3);
+
+        let undone = access.undo(2).unwrap();
+        assert_eq!(undone.iter().map(|entry| entry.address).collect::<Vec<u64>>(), vec![0xef, 0x98]);
+        assert_eq!(access.read_hw(0xef, IswRegisterWidth::U8), Ok(0));
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0x80));
+        assert_eq!(journal.entries().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn undo_stops_at_the_start_of_the_journal() {
+        let (_, mut access) = simulated_access();
+        let journal = journaled_access("too-many-steps", &mut access);
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x80).unwrap();
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x81).unwrap();
+
+        assert_eq!(access.undo(5).unwrap().len(), 2);
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0));
+        assert_eq!(journal.entries(), Ok(Vec::new()));
+        assert!(matches!(access.undo(1), Err(IswError::Usage(_))));
+    }
+
+    #[test]
+    fn undo_puts_back_the_entry_it_failed_on() {
+        let (_, mut access) = simulated_access();
+        let journal = journaled_access("failed-undo", &mut access);
+        // 0xff has no second byte on the simulated EC, so writing it back fails
+        let unwritable = entry(0xff, IswRegisterWidth::U16Le, 0, 1);
+        journal.replace(&[unwritable.clone(), entry(0x98, IswRegisterWidth::U8, 0x80, 0x81)]).unwrap();
+        access.write_u8(0x98, 0x81).unwrap();
+
+        assert!(matches!(access.undo(2), Err(IswError::OutOfRange(_))));
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0x80));
+        assert_eq!(journal.entries(), Ok(vec![unwritable]));
+    }
+
+    #[test]
+    fn dry_run_undo_leaves_journal_and_ec_alone() {
+        let (_, mut access) = simulated_access();
+        let journal = journaled_access("dry-run-undo", &mut access);
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x80).unwrap();
+        let before = journal.entries().unwrap();
+
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        access.set_dry_run(Some(Arc::new(move |write: &IswPendingWrite| sink.lock().unwrap().push(write.clone()))));
+        assert_eq!(access.undo(1).unwrap().len(), 1);
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0x80));
+        assert_eq!(journal.entries(), Ok(before));
+        assert_eq!(reported.lock().unwrap().iter().map(|write| (write.old, write.new)).collect::<Vec<(u16, u16)>>(), vec![(0x80, 0)]);
+    }
+
+    #[test]
+    fn failed_write_is_not_journaled() {
+        let mut access = IswRawAccess::new(Arc::new(ReadOnlyBackend(IswSimBackend::new())));
+        let journal = journaled_access("failed-write", &mut access);
+        assert!(matches!(access.write_hw(0x98, IswRegisterWidth::U8, 0x80), Err(IswError::Io(_))));
+        assert_eq!(journal.entries(), Ok(Vec::new()));
     }
-}
\ No newline at end of file
+
+    fn ruled_access() -> (Arc<IswSimBackend>, IswRawAccess) {
+        let (backend, mut access) = simulated_access();
+        access.set_write_rules(Some(vec![IswWriteRule {
+            name: "cooler_boost_address".to_string(),
+            address: 0x98,
+            width: IswRegisterWidth::U8,
+            allowed: vec![0x00..=0x00, 0x80..=0x80],
+        }]));
+        (backend, access)
+    }
+
+    #[test]
+    fn check_write_flags_undeclared_address() {
+        let (_, access) = ruled_access();
+        assert_eq!(access.check_write(0x10, IswRegisterWidth::U8, 0),
+                   Some("Address <0x10> is not declared in the address profile".to_string()));
+        assert_eq!(access.check_write(0x98, IswRegisterWidth::U8, 0x80), None);
+    }
+
+    #[test]
+    fn check_write_flags_width_mismatch() {
+        let (_, access) = ruled_access();
+        assert_eq!(access.check_write(0x98, IswRegisterWidth::U16Le, 0x80),
+                   Some("Register <cooler_boost_address> at <0x98> is declared as U8, not U16Le".to_string()));
+        assert_eq!(access.check_register(0x98, IswRegisterWidth::U16Le), access.check_write(0x98, IswRegisterWidth::U16Le, 0x80));
+    }
+
+    #[test]
+    fn check_write_flags_value_out_of_range() {
+        let (_, access) = ruled_access();
+        assert_eq!(access.check_write(0x98, IswRegisterWidth::U8, 0x05),
+                   Some("Value <5> is not allowed for <cooler_boost_address> at <0x98>; allowed: 0, 128".to_string()));
+        assert_eq!(access.check_register(0x98, IswRegisterWidth::U8), None);
+    }
+
+    #[test]
+    fn refused_write_needs_force() {
+        let (_, access) = ruled_access();
+        assert!(matches!(access.write_hw(0x10, IswRegisterWidth::U8, 1), Err(IswError::WriteRefused { declined: false, .. })));
+        assert_eq!(access.read_hw(0x10, IswRegisterWidth::U8), Ok(0));
+    }
+
+    #[test]
+    fn forced_write_goes_ahead_once_confirmed() {
+        let (_, mut access) = ruled_access();
+        let asked = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let questions = asked.clone();
+        access.set_force(Some(Arc::new(move |reason: &str| {
+            questions.lock().unwrap().push(reason.to_string());
+            true
+        })));
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x05).unwrap();
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0x05));
+        access.write_hw(0x98, IswRegisterWidth::U8, 0x80).unwrap();
+        assert_eq!(asked.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn declined_force_leaves_register_alone() {
+        let (_, mut access) = ruled_access();
+        access.set_force(Some(Arc::new(|_: &str| false)));
+        assert!(matches!(access.write_hw(0x98, IswRegisterWidth::U8, 0x05), Err(IswError::WriteRefused { declined: true, .. })));
+        assert_eq!(access.read_hw(0x98, IswRegisterWidth::U8), Ok(0));
+    }
+}
@@ -1,6 +1,19 @@
-use crate::isw_raw_access::IswRawAccess;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use serde::Serialize;
+use crate::isw_raw_access::{IswRawAccess, IswWriteRule};
 use crate::isw_config_ops::IswConfigOps;
+use crate::isw_config_model::{AddressProfile, FanCurve, FanKind, IswConfig, RegisterSpec, StringSpec};
+use crate::isw_ec_backend::IswEcBackend;
+use crate::isw_board;
+use crate::isw_curve_file::FanCurveFile;
+use crate::isw_backup::{BackupRegister, IswBackup};
+use crate::isw_state;
+use crate::isw_dump::read_image;
+use crate::isw_error::IswError;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum UsbBacklightKind {
     Off,
     Half,
@@ -8,177 +21,867 @@ pub enum UsbBacklightKind {
     None,
 }
 
+impl UsbBacklightKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UsbBacklightKind::Off => "off",
+            UsbBacklightKind::Half => "half",
+            UsbBacklightKind::Full => "full",
+            UsbBacklightKind::None => "unknown",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FanMode {
+    Advanced,
+    Basic,
+    Auto,
+    None,
+}
+
+impl FanMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FanMode::Advanced => "advanced",
+            FanMode::Basic => "basic",
+            FanMode::Auto => "auto",
+            FanMode::None => "unknown",
+        }
+    }
+}
+
+/// Values isw reads from the EC
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IswSensor {
+    CpuTemp,
+    CpuFanSpeed,
+    CpuFanRpm,
+    GpuTemp,
+    GpuFanSpeed,
+    GpuFanRpm,
+    CoolerBoost,
+    UsbBacklight,
+    BatteryThreshold,
+    FanMode,
+}
+
+impl IswSensor {
+    /// Every sensor, in the order `status` reports them
+    pub const ALL: [IswSensor; 10] = [
+        IswSensor::CpuTemp,
+        IswSensor::CpuFanSpeed,
+        IswSensor::CpuFanRpm,
+        IswSensor::GpuTemp,
+        IswSensor::GpuFanSpeed,
+        IswSensor::GpuFanRpm,
+        IswSensor::CoolerBoost,
+        IswSensor::UsbBacklight,
+        IswSensor::BatteryThreshold,
+        IswSensor::FanMode,
+    ];
+
+    /// Sensors of CPU and GPU that change while the laptop runs
+    pub const REALTIME: [IswSensor; 6] = [
+        IswSensor::CpuTemp,
+        IswSensor::CpuFanSpeed,
+        IswSensor::CpuFanRpm,
+        IswSensor::GpuTemp,
+        IswSensor::GpuFanSpeed,
+        IswSensor::GpuFanRpm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IswSensor::CpuTemp => "cpu_temp",
+            IswSensor::CpuFanSpeed => "cpu_fan_speed",
+            IswSensor::CpuFanRpm => "cpu_fan_rpm",
+            IswSensor::GpuTemp => "gpu_temp",
+            IswSensor::GpuFanSpeed => "gpu_fan_speed",
+            IswSensor::GpuFanRpm => "gpu_fan_rpm",
+            IswSensor::CoolerBoost => "cooler_boost",
+            IswSensor::UsbBacklight => "usb_backlight",
+            IswSensor::BatteryThreshold => "battery_charging_threshold",
+            IswSensor::FanMode => "fan_mode",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            IswSensor::CpuTemp | IswSensor::GpuTemp => "°C",
+            IswSensor::CpuFanSpeed | IswSensor::GpuFanSpeed | IswSensor::BatteryThreshold => "%",
+            IswSensor::CpuFanRpm | IswSensor::GpuFanRpm => "rpm",
+            IswSensor::CoolerBoost | IswSensor::UsbBacklight | IswSensor::FanMode => "",
+        }
+    }
+}
+
+/// Decoded value of a reading
+#[derive(Clone, PartialEq, Debug, Serialize)]
+#[serde(untagged)]
+pub enum IswValue {
+    Number(u32),
+    Flag(bool),
+    Text(String),
+}
+
+impl fmt::Display for IswValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IswValue::Number(number) => write!(f, "{}", number),
+            IswValue::Flag(flag) => write!(f, "{}", flag),
+            IswValue::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// A value read from the EC together with the register content it was decoded from
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct IswReading {
+    pub name: String,
+    pub value: IswValue,
+    pub unit: String,
+    pub raw: u16,
+}
+
+/// Every sensor and setting, decoded from a single read of the EC
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct IswSnapshot {
+    /// Readings in the order of `IswSensor::ALL`
+    pub readings: Vec<IswReading>,
+}
+
+impl IswSnapshot {
+    pub fn reading(&self, sensor: IswSensor) -> Option<&IswReading> {
+        self.readings.iter().find(|reading| reading.name == sensor.name())
+    }
+}
+
+/// Firmware identifier and build timestamp stored in the EC
+#[derive(Clone, Debug)]
+pub struct FirmwareInfo {
+    pub version: String,
+    pub date: String,
+    pub time: String,
+}
+
 #[derive(Clone)]
 pub struct IswRsBase {
     pub raw_access: IswRawAccess,
     m_config_ops: IswConfigOps,
+    m_config: IswConfig,
 }
 
 impl IswRsBase {
-    const MSI_ADDRESS_DEFAULT: &'static str = "MSI_ADDRESS_DEFAULT";
-    const COOLER_BOOST: &'static str = "COOLER_BOOST";
-    const COOLER_BOOST_OFF: &'static str = "cooler_boost_off";
-    const COOLER_BOOST_ON: &'static str = "cooler_boost_on";
-    const COOLER_BOOST_ADDRESS_IDENTIFIER: &'static str = "cooler_boost_address";
-    const BATTERY_CHARGING_THRESHOLD_ADDRESS_IDENTIFIER: &'static str = "battery_charging_threshold_address";
-    const USB_BACKLIGHT: &'static str = "USB_BACKLIGHT";
-    const USB_BACKLIGHT_ADDRESS_IDENTIFIER: &'static str = "usb_backlight_address";
-    const USB_BACKLIGHT_OFF: &'static str = "usb_backlight_off";
-    const USB_BACKLIGHT_HALF: &'static str = "usb_backlight_half";
-    const USB_BACKLIGHT_FULL: &'static str = "usb_backlight_full";
-    const GPU_TEMP_ADDRESS_IDENTIFIER: &'static str = "realtime_gpu_temp_address";
-    const CPU_TEMP_ADDRESS_IDENTIFIER: &'static str = "realtime_cpu_temp_address";
-    const GPU_FAN_SPEED_ADDRESS_IDENTIFIER: &'static str = "realtime_gpu_fan_speed_address";
-    const CPU_FAN_SPEED_ADDRESS_IDENTIFIER: &'static str = "realtime_cpu_fan_speed_address";
-    const GPU_FAN_RPM_ADDRESS_IDENTIFIER: &'static str = "realtime_gpu_fan_rpm_address";
-    const CPU_FAN_RPM_ADDRESS_IDENTIFIER: &'static str = "realtime_cpu_fan_rpm_address";
-    const IO_FILE: &'static str = "/sys/kernel/debug/ec/ec0/io";
-
     const FAN_DIVISOR_CONSTANT: u32 = 478000;
+    const BATTERY_THRESHOLD_MIN: u16 = 20;
+    const BATTERY_THRESHOLD_MAX: u16 = 100;
+    const BATTERY_THRESHOLD_OFFSET: u16 = 128;
+    const ADDRESS_SUFFIX: &'static str = "_address";
+    const ADDRESS_INFIX: &'static str = "_address_";
 
-    pub fn new(cfg_file: String) -> Result<IswRsBase, String> {
-        let mut s = IswRsBase {
-            raw_access: IswRawAccess::new(IswRsBase::IO_FILE.to_string()),
-            m_config_ops: IswConfigOps::new(cfg_file),
+    pub fn new(cfg_file: Option<String>, backend: Arc<dyn IswEcBackend>) -> Result<IswRsBase, IswError> {
+        let mut config_ops = IswConfigOps::new(cfg_file);
+        config_ops.load_config()?;
+        IswRsBase::with_config_ops(config_ops, backend)
+    }
+
+    fn with_config_ops(config_ops: IswConfigOps, backend: Arc<dyn IswEcBackend>) -> Result<IswRsBase, IswError> {
+        let config = IswConfig::load(&config_ops)?;
+
+        let mut isw = IswRsBase {
+            raw_access: IswRawAccess::new(backend),
+            m_config_ops: config_ops,
+            m_config: config,
         };
+        let rules = isw.get_write_rules()?;
+        isw.raw_access.set_write_rules(Some(rules));
+        Ok(isw)
+    }
 
-        s.m_config_ops.load_config()?;
+    /// Write `value` to the register described by `spec`
+    fn write_register(&self, spec: RegisterSpec, value: u16) -> Result<(), IswError> {
+        self.raw_access.write_hw(spec.address, spec.width, value)
+    }
 
-        return Ok(s);
+    /// Read the register described by `spec`
+    fn read_register(&self, spec: RegisterSpec) -> Result<u16, IswError> {
+        self.raw_access.read_hw(spec.address, spec.width)
     }
-    /// set USB Backlight
-    pub fn set_usb_backlight(&mut self, state: UsbBacklightKind) -> Result<(), String> {
-        let value: u64;
-        let base_address = self.m_config_ops.get_base_address(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_ADDRESS_IDENTIFIER.to_string())?;
 
-        match state {
-            UsbBacklightKind::Off => {
-                value = self.m_config_ops.get_numeric_property(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_OFF.to_string())?;
-            }
-            UsbBacklightKind::Half => {
-                value = self.m_config_ops.get_numeric_property(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_HALF.to_string())?;
+    /// Address profile of a laptop section, or the address profile named `section` itself
+    fn get_address_profile(&self, section: &str) -> Result<&AddressProfile, IswError> {
+        match self.m_config.laptops.get(section) {
+            Some(laptop) => self.m_config.address_profile(laptop.address_profile.as_str()),
+            None => self.m_config.address_profile(section),
+        }
+    }
+
+    fn default_profile(&self) -> Result<&AddressProfile, IswError> {
+        self.m_config.address_profile(IswConfig::MSI_ADDRESS_DEFAULT)
+    }
+
+    /// Registers of the active address profile and the values isw writes to them
+    pub fn get_write_rules(&self) -> Result<Vec<IswWriteRule>, IswError> {
+        let exact = |values: &[u8]| values.iter().map(|value| *value as u16..=*value as u16).collect::<Vec<_>>();
+        let rule = |name: &str, register: RegisterSpec, allowed: Vec<RangeInclusive<u16>>| IswWriteRule {
+            name: name.to_string(),
+            address: register.address,
+            width: register.width,
+            allowed,
+        };
+
+        let cooler_boost = &self.m_config.cooler_boost;
+        let usb_backlight = &self.m_config.usb_backlight;
+        let fan_mode = &self.m_config.fan_mode;
+        let profile = self.default_profile()?;
+        let mut rules = vec![
+            rule("cooler_boost_address",
+                 self.m_config.address_profile(cooler_boost.address_profile.as_str())?.cooler_boost,
+                 exact(&[cooler_boost.off, cooler_boost.on])),
+            rule("usb_backlight_address",
+                 self.m_config.address_profile(usb_backlight.address_profile.as_str())?.usb_backlight,
+                 exact(&[usb_backlight.off, usb_backlight.half, usb_backlight.full])),
+            rule("fan_mode_address",
+                 self.m_config.address_profile(fan_mode.address_profile.as_str())?.fan_mode,
+                 exact(&[fan_mode.advanced, fan_mode.basic, fan_mode.auto])),
+            rule("battery_charging_threshold_address", profile.battery_charging_threshold,
+                 vec![IswRsBase::BATTERY_THRESHOLD_MIN + IswRsBase::BATTERY_THRESHOLD_OFFSET
+                      ..=IswRsBase::BATTERY_THRESHOLD_MAX + IswRsBase::BATTERY_THRESHOLD_OFFSET]),
+        ];
+        for kind in [FanKind::Cpu, FanKind::Gpu].iter() {
+            let addresses = profile.curve(*kind);
+            for (i, register) in addresses.temps.iter().enumerate() {
+                let name = FanCurve::address_key(FanCurve::temp_key(*kind, i));
+                rules.push(rule(name.as_str(), *register, vec![0..=FanCurve::MAX_TEMP as u16]));
             }
-            UsbBacklightKind::Full => {
-                value = self.m_config_ops.get_numeric_property(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_FULL.to_string())?;
+            for (i, register) in addresses.speeds.iter().enumerate() {
+                let name = FanCurve::address_key(FanCurve::speed_key(*kind, i));
+                rules.push(rule(name.as_str(), *register, vec![0..=FanCurve::MAX_SPEED as u16]));
             }
+        }
+        Ok(rules)
+    }
+
+    /// set USB Backlight
+    pub fn set_usb_backlight(&mut self, state: UsbBacklightKind) -> Result<(), IswError> {
+        let spec = &self.m_config.usb_backlight;
+        let value = match state {
+            UsbBacklightKind::Off => spec.off,
+            UsbBacklightKind::Half => spec.half,
+            UsbBacklightKind::Full => spec.full,
             _ => {
-                return Result::Err("No viable option provided".to_string());
+                return Err(IswError::Usage("No viable option provided".to_string()));
             }
-        }
+        };
 
-        self.raw_access.write_hw(base_address, value as u16)?;
+        let register = self.m_config.address_profile(spec.address_profile.as_str())?.usb_backlight;
+        self.write_register(register, value as u16)?;
         Ok(())
     }
-    pub fn get_usb_backlight(&mut self) -> Result<UsbBacklightKind, String> {
-        let base_address = self.m_config_ops.get_base_address(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_ADDRESS_IDENTIFIER.to_string())?;
-
-        let half = self.m_config_ops.get_numeric_property(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_HALF.to_string())? as u16;
-        let full = self.m_config_ops.get_numeric_property(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_FULL.to_string())? as u16;
-        let off = self.m_config_ops.get_numeric_property(IswRsBase::USB_BACKLIGHT.to_string(), IswRsBase::USB_BACKLIGHT_OFF.to_string())? as u16;
+    fn usb_backlight_from_raw(&self, value: u16) -> UsbBacklightKind {
+        let spec = &self.m_config.usb_backlight;
+        if value == spec.half as u16 {
+            return UsbBacklightKind::Half;
+        } else if value == spec.full as u16 {
+            return UsbBacklightKind::Full;
+        } else if value == spec.off as u16 {
+            return UsbBacklightKind::Off;
+        }
+        UsbBacklightKind::None
+    }
+    /// get USB Backlight
+    #[allow(dead_code)]
+    pub fn get_usb_backlight(&self) -> Result<UsbBacklightKind, IswError> {
+        let reading = self.read_sensor(IswSensor::UsbBacklight)?;
+        Ok(self.usb_backlight_from_raw(reading.raw))
+    }
 
-        let value = self.raw_access.read_hw(base_address)?;
+    /// set Fan mode
+    pub fn set_fan_mode(&mut self, mode: FanMode) -> Result<(), IswError> {
+        let spec = &self.m_config.fan_mode;
+        let value = match mode {
+            FanMode::Advanced => spec.advanced,
+            FanMode::Basic => spec.basic,
+            FanMode::Auto => spec.auto,
+            _ => {
+                return Err(IswError::Usage("No viable option provided".to_string()));
+            }
+        };
 
-        if value == half {
-            return Ok(UsbBacklightKind::Half);
-        } else if value == full {
-            return Ok(UsbBacklightKind::Full);
-        } else if value == off {
-            return Ok(UsbBacklightKind::Off);
+        let register = self.m_config.address_profile(spec.address_profile.as_str())?.fan_mode;
+        self.write_register(register, value as u16)?;
+        Ok(())
+    }
+    fn fan_mode_from_raw(&self, value: u16) -> FanMode {
+        let spec = &self.m_config.fan_mode;
+        if value == spec.advanced as u16 {
+            return FanMode::Advanced;
+        } else if value == spec.basic as u16 {
+            return FanMode::Basic;
+        } else if value == spec.auto as u16 {
+            return FanMode::Auto;
         }
-        Ok(UsbBacklightKind::None)
+        FanMode::None
+    }
+    /// get Fan mode
+    #[allow(dead_code)]
+    pub fn get_fan_mode(&self) -> Result<FanMode, IswError> {
+        let reading = self.read_sensor(IswSensor::FanMode)?;
+        Ok(self.fan_mode_from_raw(reading.raw))
     }
 
     /// set Battery Threshold
-    pub fn set_battery_threshold(&mut self, t: u8) -> Result<(), String> {
-        if t < 20 && t > 100 {
-            return Err("No viable threshold provided".to_string());
+    pub fn set_battery_threshold(&mut self, t: u8) -> Result<(), IswError> {
+        if !(IswRsBase::BATTERY_THRESHOLD_MIN..=IswRsBase::BATTERY_THRESHOLD_MAX).contains(&(t as u16)) {
+            return Err(IswError::OutOfRange("No viable threshold provided".to_string()));
         }
-        let base_address = self.m_config_ops.get_numeric_property(IswRsBase::MSI_ADDRESS_DEFAULT.to_string(), IswRsBase::BATTERY_CHARGING_THRESHOLD_ADDRESS_IDENTIFIER.to_string())?;
-        self.raw_access.write_hw(base_address, (t as u16) + 128)?;
+        let register = self.default_profile()?.battery_charging_threshold;
+        self.write_register(register, (t as u16) + IswRsBase::BATTERY_THRESHOLD_OFFSET)?;
         Ok(())
     }
-    pub fn get_battery_threshold(&mut self) -> Result<u8, String> {
-        let base_address = self.m_config_ops.get_numeric_property(IswRsBase::MSI_ADDRESS_DEFAULT.to_string(), IswRsBase::BATTERY_CHARGING_THRESHOLD_ADDRESS_IDENTIFIER.to_string())?;
-        let read = self.raw_access.read_hw(base_address)? - 128;
-        Ok(read as u8)
+    fn battery_threshold_from_raw(value: u16) -> u16 {
+        value.saturating_sub(IswRsBase::BATTERY_THRESHOLD_OFFSET)
+    }
+    /// get Battery Threshold
+    #[allow(dead_code)]
+    pub fn get_battery_threshold(&self) -> Result<u8, IswError> {
+        let reading = self.read_sensor(IswSensor::BatteryThreshold)?;
+        Ok(IswRsBase::battery_threshold_from_raw(reading.raw) as u8)
     }
 
     /// Set Coolerboost
-    pub fn set_cooler_boost(&mut self, on: bool) -> Result<(), String> {
-        let value: u64;
-        let base_address = self.m_config_ops.get_base_address(IswRsBase::COOLER_BOOST.to_string(), IswRsBase::COOLER_BOOST_ADDRESS_IDENTIFIER.to_string());
-
-        if on {
-            value = self.m_config_ops.get_numeric_property(IswRsBase::COOLER_BOOST.to_string(), IswRsBase::COOLER_BOOST_ON.to_string())?;
-        } else {
-            value = self.m_config_ops.get_numeric_property(IswRsBase::COOLER_BOOST.to_string(), IswRsBase::COOLER_BOOST_OFF.to_string())?;
-        }
-        self.raw_access.write_hw(base_address.unwrap(), value as u16)?;
+    pub fn set_cooler_boost(&mut self, on: bool) -> Result<(), IswError> {
+        let spec = &self.m_config.cooler_boost;
+        let value = if on { spec.on } else { spec.off };
+        let register = self.m_config.address_profile(spec.address_profile.as_str())?.cooler_boost;
+        self.write_register(register, value as u16)?;
         Ok(())
     }
-    pub fn get_cooler_boost(&mut self) -> Result<bool, String> {
-        let base_address = self.m_config_ops.get_base_address(IswRsBase::COOLER_BOOST.to_string(), IswRsBase::COOLER_BOOST_ADDRESS_IDENTIFIER.to_string())?;
-        let is_on = self.m_config_ops.get_numeric_property(IswRsBase::COOLER_BOOST.to_string(), IswRsBase::COOLER_BOOST_ON.to_string())?;
-
-        if self.raw_access.read_hw(base_address)? == (is_on as u16) {
-            return Ok(true);
-        }
-        Ok(false)
+    fn cooler_boost_from_raw(&self, value: u16) -> bool {
+        value == self.m_config.cooler_boost.on as u16
+    }
+    /// get Coolerboost
+    #[allow(dead_code)]
+    pub fn get_cooler_boost(&self) -> Result<bool, IswError> {
+        let reading = self.read_sensor(IswSensor::CoolerBoost)?;
+        Ok(self.cooler_boost_from_raw(reading.raw))
     }
 
-    fn get_data<T: num::NumCast>(&self, address_of: String) -> Result<T, String> {
-        let base_address = self.m_config_ops.get_base_address(IswRsBase::MSI_ADDRESS_DEFAULT.to_string(), address_of)?;
-        let read = self.raw_access.read_hw(base_address)?;
+    fn get_data<T: num::NumCast>(&self, register: fn(&AddressProfile) -> RegisterSpec) -> Result<T, IswError> {
+        let read = self.read_register(register(self.default_profile()?))?;
         Ok(num::cast(read).unwrap())
     }
 
-    fn get_temp(&mut self, address_of: String) -> Result<f64, String> {
-        let temp = self.get_data(address_of)?;
+    fn get_temp(&mut self, register: fn(&AddressProfile) -> RegisterSpec) -> Result<f64, IswError> {
+        let temp = self.get_data(register)?;
         Ok(temp)
     }
 
-    pub fn get_gpu_temp(&mut self) -> Result<f64, String> {
-        let temp = self.get_temp(IswRsBase::GPU_TEMP_ADDRESS_IDENTIFIER.to_string())?;
+    pub fn get_gpu_temp(&mut self) -> Result<f64, IswError> {
+        let temp = self.get_temp(|profile| profile.realtime_gpu_temp)?;
         Ok(temp)
     }
 
-    pub fn get_cpu_temp(&mut self) -> Result<f64, String> {
-        let temp = self.get_temp(IswRsBase::CPU_TEMP_ADDRESS_IDENTIFIER.to_string())?;
+    pub fn get_cpu_temp(&mut self) -> Result<f64, IswError> {
+        let temp = self.get_temp(|profile| profile.realtime_cpu_temp)?;
         Ok(temp)
     }
 
-    fn get_fan_speed(&mut self, address_of: String) -> Result<u16, String> {
-        let speed: u16 = self.get_data(address_of)?;
-        // We are reading two bytes, but only need the last nibble...
-        let corrected = speed & 0x000000FF;
-        Ok(corrected)
+    fn get_fan_speed(&mut self, register: fn(&AddressProfile) -> RegisterSpec) -> Result<u16, IswError> {
+        let speed: u16 = self.get_data(register)?;
+        Ok(speed)
     }
 
-    pub fn get_gpu_fan_speed(&mut self) -> Result<u16, String> {
-        let speed = self.get_fan_speed(IswRsBase::GPU_FAN_SPEED_ADDRESS_IDENTIFIER.to_string())?;
+    pub fn get_gpu_fan_speed(&mut self) -> Result<u16, IswError> {
+        let speed = self.get_fan_speed(|profile| profile.realtime_gpu_fan_speed)?;
         Ok(speed)
     }
 
-    pub fn get_cpu_fan_speed(&mut self) -> Result<u16, String> {
-        let speed = self.get_fan_speed(IswRsBase::CPU_FAN_SPEED_ADDRESS_IDENTIFIER.to_string())?;
+    pub fn get_cpu_fan_speed(&mut self) -> Result<u16, IswError> {
+        let speed = self.get_fan_speed(|profile| profile.realtime_cpu_fan_speed)?;
         Ok(speed)
     }
 
-    fn get_fan_rpm(&mut self, address_of: String) -> Result<u16, String> {
-        let value: u32 = self.get_data(address_of)?;
+    fn fan_rpm_from_raw(value: u16) -> u16 {
         if value == 0 {
-            return Ok(0);
+            return 0;
         }
-        Ok((IswRsBase::FAN_DIVISOR_CONSTANT / value) as u16)
+        (IswRsBase::FAN_DIVISOR_CONSTANT / value as u32) as u16
     }
 
-    pub fn get_gpu_fan_rpm(&mut self) -> Result<u16, String> {
-        let rpm = self.get_fan_rpm(IswRsBase::GPU_FAN_RPM_ADDRESS_IDENTIFIER.to_string())?;
+    fn get_fan_rpm(&mut self, register: fn(&AddressProfile) -> RegisterSpec) -> Result<u16, IswError> {
+        let value: u16 = self.get_data(register)?;
+        Ok(IswRsBase::fan_rpm_from_raw(value))
+    }
+
+    pub fn get_gpu_fan_rpm(&mut self) -> Result<u16, IswError> {
+        let rpm = self.get_fan_rpm(|profile| profile.realtime_gpu_fan_rpm)?;
         Ok(rpm)
     }
 
-    pub fn get_cpu_fan_rpm(&mut self) -> Result<u16, String> {
-        let rpm = self.get_fan_rpm(IswRsBase::CPU_FAN_RPM_ADDRESS_IDENTIFIER.to_string())?;
+    pub fn get_cpu_fan_rpm(&mut self) -> Result<u16, IswError> {
+        let rpm = self.get_fan_rpm(|profile| profile.realtime_cpu_fan_rpm)?;
         Ok(rpm)
     }
-}
\ No newline at end of file
+
+    fn sensor_register(&self, sensor: IswSensor) -> Result<RegisterSpec, IswError> {
+        let profile = self.default_profile()?;
+        let register = match sensor {
+            IswSensor::CpuTemp => profile.realtime_cpu_temp,
+            IswSensor::CpuFanSpeed => profile.realtime_cpu_fan_speed,
+            IswSensor::CpuFanRpm => profile.realtime_cpu_fan_rpm,
+            IswSensor::GpuTemp => profile.realtime_gpu_temp,
+            IswSensor::GpuFanSpeed => profile.realtime_gpu_fan_speed,
+            IswSensor::GpuFanRpm => profile.realtime_gpu_fan_rpm,
+            IswSensor::CoolerBoost => {
+                self.m_config.address_profile(self.m_config.cooler_boost.address_profile.as_str())?.cooler_boost
+            }
+            IswSensor::UsbBacklight => {
+                self.m_config.address_profile(self.m_config.usb_backlight.address_profile.as_str())?.usb_backlight
+            }
+            IswSensor::BatteryThreshold => profile.battery_charging_threshold,
+            IswSensor::FanMode => {
+                self.m_config.address_profile(self.m_config.fan_mode.address_profile.as_str())?.fan_mode
+            }
+        };
+        Ok(register)
+    }
+
+    fn decode_sensor(&self, sensor: IswSensor, raw: u16) -> IswReading {
+        let value = match sensor {
+            IswSensor::CpuTemp | IswSensor::GpuTemp | IswSensor::CpuFanSpeed | IswSensor::GpuFanSpeed => {
+                IswValue::Number(raw as u32)
+            }
+            IswSensor::CpuFanRpm | IswSensor::GpuFanRpm => IswValue::Number(IswRsBase::fan_rpm_from_raw(raw) as u32),
+            IswSensor::CoolerBoost => IswValue::Flag(self.cooler_boost_from_raw(raw)),
+            IswSensor::UsbBacklight => IswValue::Text(self.usb_backlight_from_raw(raw).name().to_string()),
+            IswSensor::BatteryThreshold => IswValue::Number(IswRsBase::battery_threshold_from_raw(raw) as u32),
+            IswSensor::FanMode => IswValue::Text(self.fan_mode_from_raw(raw).name().to_string()),
+        };
+        IswReading {
+            name: sensor.name().to_string(),
+            value,
+            unit: sensor.unit().to_string(),
+            raw,
+        }
+    }
+
+    /// Read `sensor` from the EC, keeping the raw register value next to the decoded one
+    pub fn read_sensor(&self, sensor: IswSensor) -> Result<IswReading, IswError> {
+        let raw = self.read_register(self.sensor_register(sensor)?)?;
+        Ok(self.decode_sensor(sensor, raw))
+    }
+
+    /// Read every sensor and setting from one dump of the EC
+    pub fn snapshot(&self) -> Result<IswSnapshot, IswError> {
+        let image = self.dump()?;
+        let mut readings = Vec::new();
+        for sensor in IswSensor::ALL.iter() {
+            let register = self.sensor_register(*sensor)?;
+            let raw = read_image(&image, register.address, register.width)?;
+            readings.push(self.decode_sensor(*sensor, raw));
+        }
+        Ok(IswSnapshot { readings })
+    }
+
+    /// Read the whole EC register space in one pass
+    pub fn dump(&self) -> Result<Vec<u8>, IswError> {
+        self.raw_access.dump_hw()
+    }
+
+    /// Snapshot of every register isw writes to, located through the address profile of `section`
+    pub fn backup(&self, section: String) -> Result<IswBackup, IswError> {
+        let profile = self.get_address_profile(section.as_str())?;
+        let mut registers = Vec::new();
+        for (name, spec) in profile.writable_registers() {
+            registers.push(BackupRegister {
+                name,
+                address: spec.address,
+                width: spec.width,
+                value: self.read_register(spec)?,
+            });
+        }
+        Ok(IswBackup {
+            created: isw_state::timestamp(),
+            address_profile: profile.name.clone(),
+            registers,
+        })
+    }
+
+    /// Write every register of `backup` back to the EC; the values were read from it, so only
+    /// their allowed ranges are not checked, registers outside the write rules still need --force
+    pub fn restore(&mut self, backup: &IswBackup) -> Result<(), IswError> {
+        let profile = self.default_profile()?;
+        if backup.address_profile != profile.name {
+            return Err(IswError::Usage("Backup was taken with address profile <".to_string()
+                + backup.address_profile.as_str() + ">, but <" + profile.name.as_str() + "> is active"));
+        }
+        let writable = profile.writable_registers();
+        for register in backup.registers.iter() {
+            IswRawAccess::check_value(register.address, register.width, register.value)?;
+        }
+        // every register is checked before the first write, so a refused one leaves the EC untouched
+        for register in backup.registers.iter() {
+            let listed = writable.iter().any(|(_, spec)| spec.address == register.address && spec.width == register.width);
+            let reason = match self.raw_access.check_register(register.address, register.width) {
+                Some(reason) => Some(reason),
+                None if !listed => Some("Register <".to_string() + register.name.as_str() + "> at <"
+                    + format!("0x{:02x}", register.address).as_str() + "> is not a writable register of address profile <"
+                    + profile.name.as_str() + ">"),
+                None => None,
+            };
+            if let Some(reason) = reason {
+                self.raw_access.confirm_forced(reason)?;
+            }
+        }
+        for register in backup.registers.iter() {
+            self.raw_access.write_hw_trusted(register.address, register.width, register.value)?;
+        }
+        Ok(())
+    }
+
+    /// Every register named in the address profile of `section`, sorted by address
+    pub fn get_named_addresses(&self, section: String) -> Result<Vec<(u64, String)>, IswError> {
+        let profile = self.m_config_ops.get_address_profile(section.clone())?;
+        let properties = self.m_config_ops.get_section(profile)?;
+        let mut named = Vec::new();
+
+        for key in properties.keys() {
+            let is_address = key.ends_with(IswRsBase::ADDRESS_SUFFIX) || match key.rfind(IswRsBase::ADDRESS_INFIX) {
+                None => false,
+                Some(pos) => key[pos + IswRsBase::ADDRESS_INFIX.len()..].parse::<u8>().is_ok()
+            };
+            if is_address {
+                let address = self.m_config_ops.get_base_address(section.clone(), key.clone())?;
+                named.push((address, key.clone()));
+            }
+        }
+
+        named.sort();
+        Ok(named)
+    }
+
+    /// Fan curve of `kind` as listed in the board section `section`
+    pub fn get_config_fan_curve(&self, section: String, kind: FanKind) -> Result<FanCurve, IswError> {
+        Ok(self.m_config.laptop(section.as_str())?.curve(kind).clone())
+    }
+
+    /// Fan curve of `kind` currently stored in the EC, read through the address profile of `section`
+    pub fn get_fan_curve(&self, section: String, kind: FanKind) -> Result<FanCurve, IswError> {
+        let addresses = self.get_address_profile(section.as_str())?.curve(kind);
+        let mut curve = FanCurve { temps: Vec::new(), speeds: Vec::new() };
+        for register in addresses.temps.iter() {
+            curve.temps.push(self.read_register(*register)? as u8);
+        }
+        for register in addresses.speeds.iter() {
+            curve.speeds.push(self.read_register(*register)? as u8);
+        }
+        Ok(curve)
+    }
+
+    /// Write `curve` to the fan curve registers of `kind` listed in the address profile of `section`
+    pub fn set_fan_curve(&mut self, section: String, kind: FanKind, curve: &FanCurve) -> Result<(), IswError> {
+        curve.validate()?;
+        let addresses = self.get_address_profile(section.as_str())?.curve(kind);
+        for (register, temp) in addresses.temps.iter().zip(curve.temps.iter()) {
+            self.write_register(*register, *temp as u16)?;
+        }
+        for (register, speed) in addresses.speeds.iter().zip(curve.speeds.iter()) {
+            self.write_register(*register, *speed as u16)?;
+        }
+        Ok(())
+    }
+
+    /// Write the CPU and GPU fan curves of the board section `section` to the EC
+    pub fn apply_fan_curve(&mut self, section: String) -> Result<(), IswError> {
+        let cpu = self.get_config_fan_curve(section.clone(), FanKind::Cpu)?;
+        let gpu = self.get_config_fan_curve(section.clone(), FanKind::Gpu)?;
+        // both before the first write, so a bad GPU curve can't leave a new CPU curve behind
+        cpu.validate()?;
+        gpu.validate()?;
+        self.set_fan_curve(section.clone(), FanKind::Cpu, &cpu)?;
+        self.set_fan_curve(section, FanKind::Gpu, &gpu)?;
+        Ok(())
+    }
+
+    /// CPU and GPU fan curves of the board section `section`, read from the EC if `from_ec`
+    pub fn export_fan_curves(&self, section: String, from_ec: bool) -> Result<FanCurveFile, IswError> {
+        let (cpu, gpu) = if from_ec {
+            (self.get_fan_curve(section.clone(), FanKind::Cpu)?, self.get_fan_curve(section.clone(), FanKind::Gpu)?)
+        } else {
+            (self.get_config_fan_curve(section.clone(), FanKind::Cpu)?, self.get_config_fan_curve(section.clone(), FanKind::Gpu)?)
+        };
+        Ok(FanCurveFile { section, cpu, gpu })
+    }
+
+    /// Write the fan curves of `curves` to the EC through the address profile of `section`
+    pub fn import_fan_curves(&mut self, section: String, curves: &FanCurveFile) -> Result<(), IswError> {
+        // both before the first write, so a bad GPU curve can't leave a new CPU curve behind
+        curves.cpu.validate()?;
+        curves.gpu.validate()?;
+        self.set_fan_curve(section.clone(), FanKind::Cpu, &curves.cpu)?;
+        self.set_fan_curve(section, FanKind::Gpu, &curves.gpu)?;
+        Ok(())
+    }
+
+    /// Store `curve` as fan curve of `kind` in the board section `section` of the config file
+    pub fn save_fan_curve(&mut self, section: String, kind: FanKind, curve: &FanCurve) -> Result<(), IswError> {
+        curve.validate()?;
+        self.m_config.laptop(section.as_str())?;
+        for (i, temp) in curve.temps.iter().enumerate() {
+            self.m_config_ops.set_property(section.clone(), FanCurve::temp_key(kind, i), temp.to_string())?;
+        }
+        for (i, speed) in curve.speeds.iter().enumerate() {
+            self.m_config_ops.set_property(section.clone(), FanCurve::speed_key(kind, i), speed.to_string())?;
+        }
+        self.m_config_ops.save_config()?;
+        self.m_config = IswConfig::load(&self.m_config_ops)?;
+        Ok(())
+    }
+
+    /// Store the CPU and GPU fan curves currently in the EC in the board section `section` of the config file
+    pub fn save_active_fan_curve(&mut self, section: String) -> Result<(), IswError> {
+        let cpu = self.get_fan_curve(section.clone(), FanKind::Cpu)?;
+        let gpu = self.get_fan_curve(section.clone(), FanKind::Gpu)?;
+        self.save_fan_curve(section.clone(), FanKind::Cpu, &cpu)?;
+        self.save_fan_curve(section, FanKind::Gpu, &gpu)?;
+        Ok(())
+    }
+
+    /// Sections describing a laptop board, i.e. the ones carrying a fan curve
+    pub fn get_board_sections(&self) -> Vec<String> {
+        self.m_config.laptops.keys().cloned().collect()
+    }
+
+    /// Board section matching the DMI board name found below `sysfs_root`
+    pub fn detect_section(&self, sysfs_root: String) -> Result<String, IswError> {
+        let board_name = isw_board::read_board_name(sysfs_root)?;
+        isw_board::match_board_section(board_name, &self.get_board_sections())
+    }
+
+    /// Printable ASCII text of `spec`, up to the first NUL; anything else is shown as hex in the error
+    fn get_string(&self, spec: StringSpec) -> Result<String, IswError> {
+        let bytes = self.raw_access.read_block(spec.address, spec.length)?;
+        let text: Vec<u8> = bytes.into_iter().take_while(|byte| *byte != 0).collect();
+        if !text.iter().all(|byte| byte.is_ascii_graphic() || *byte == b' ') {
+            let hex: Vec<String> = text.iter().map(|byte| format!("{:02x}", byte)).collect();
+            return Err(IswError::Parse(format!("Unreadable EC string at <0x{:02x}>: {}", spec.address, hex.join(" "))));
+        }
+        Ok(String::from_utf8_lossy(&text).trim().to_string())
+    }
+
+    /// Firmware identifier (e.g. "16J9EMS1.109") and build date/time of the EC
+    pub fn get_firmware_info(&self) -> Result<FirmwareInfo, IswError> {
+        let profile = self.default_profile()?;
+        Ok(FirmwareInfo {
+            version: self.get_string(profile.fw_version)?,
+            date: self.get_string(profile.fw_date)?,
+            time: self.get_string(profile.fw_time)?,
+        })
+    }
+
+    /// Warning if the running firmware doesn't belong to the board section `section`
+    pub fn check_firmware(&self, section: String) -> Result<Option<String>, IswError> {
+        let version = match self.get_string(self.default_profile()?.fw_version) {
+            Ok(version) => version,
+            Err(IswError::Parse(_)) => {
+                return Ok(Some("Unreadable firmware id; cannot tell whether the EC belongs to section <".to_string()
+                    + section.as_str() + ">"));
+            }
+            Err(error) => return Err(error),
+        };
+        let firmware_board = version.split('.').next().unwrap_or("").to_string();
+        if firmware_board.eq_ignore_ascii_case(section.as_str()) {
+            return Ok(None);
+        }
+        Ok(Some("EC firmware <".to_string() + version.as_str()
+            + "> does not belong to section <" + section.as_str() + ">"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isw_raw_access::IswRegisterWidth;
+    use crate::isw_sim_backend::IswSimBackend;
+
+    fn simulated_isw() -> IswRsBase {
+        simulated_isw_on(Arc::new(IswSimBackend::new()))
+    }
+
+    fn simulated_isw_on(backend: Arc<IswSimBackend>) -> IswRsBase {
+        let config_ops = IswConfigOps::from_text(include_str!("../isw.conf"));
+        IswRsBase::with_config_ops(config_ops, backend).unwrap()
+    }
+
+    #[test]
+    fn cooler_boost_round_trip() {
+        let mut isw = simulated_isw();
+        isw.set_cooler_boost(true).unwrap();
+        assert_eq!(isw.get_cooler_boost(), Ok(true));
+        isw.set_cooler_boost(false).unwrap();
+        assert_eq!(isw.get_cooler_boost(), Ok(false));
+    }
+
+    #[test]
+    fn usb_backlight_round_trip() {
+        let mut isw = simulated_isw();
+        for state in [UsbBacklightKind::Off, UsbBacklightKind::Half, UsbBacklightKind::Full].iter() {
+            isw.set_usb_backlight(*state).unwrap();
+            assert_eq!(isw.get_usb_backlight(), Ok(*state));
+        }
+        assert!(matches!(isw.set_usb_backlight(UsbBacklightKind::None), Err(IswError::Usage(_))));
+    }
+
+    #[test]
+    fn battery_threshold_round_trip() {
+        let mut isw = simulated_isw();
+        isw.set_battery_threshold(60).unwrap();
+        assert_eq!(isw.get_battery_threshold(), Ok(60));
+        assert_eq!(isw.read_sensor(IswSensor::BatteryThreshold).unwrap().raw, 60 + IswRsBase::BATTERY_THRESHOLD_OFFSET);
+    }
+
+    #[test]
+    fn battery_threshold_out_of_range_is_not_written() {
+        let mut isw = simulated_isw();
+        isw.set_battery_threshold(60).unwrap();
+        assert!(matches!(isw.set_battery_threshold(10), Err(IswError::OutOfRange(_))));
+        assert!(matches!(isw.set_battery_threshold(101), Err(IswError::OutOfRange(_))));
+        assert_eq!(isw.get_battery_threshold(), Ok(60));
+    }
+
+    #[test]
+    fn fan_mode_round_trip() {
+        let mut isw = simulated_isw();
+        for mode in [FanMode::Advanced, FanMode::Basic, FanMode::Auto].iter() {
+            isw.set_fan_mode(*mode).unwrap();
+            assert_eq!(isw.get_fan_mode(), Ok(*mode));
+        }
+    }
+
+    #[test]
+    fn snapshot_matches_single_reads() {
+        let mut isw = simulated_isw();
+        isw.set_cooler_boost(true).unwrap();
+        isw.set_fan_mode(FanMode::Advanced).unwrap();
+        isw.set_battery_threshold(80).unwrap();
+        let snapshot = isw.snapshot().unwrap();
+        assert_eq!(snapshot.readings.len(), IswSensor::ALL.len());
+        for sensor in IswSensor::ALL.iter() {
+            assert_eq!(snapshot.reading(*sensor), Some(&isw.read_sensor(*sensor).unwrap()));
+        }
+        assert_eq!(snapshot.reading(IswSensor::FanMode).unwrap().value, IswValue::Text("advanced".to_string()));
+    }
+
+    #[test]
+    fn firmware_info_reads_ascii() {
+        let backend = Arc::new(IswSimBackend::new());
+        let isw = simulated_isw_on(backend.clone());
+        let profile = isw.default_profile().unwrap().clone();
+        backend.write(profile.fw_version.address, b"16J9EMS1.109\0").unwrap();
+        backend.write(profile.fw_date.address, b"08112021").unwrap();
+        backend.write(profile.fw_time.address, b"15:06:26").unwrap();
+        let info = isw.get_firmware_info().unwrap();
+        assert_eq!((info.version.as_str(), info.date.as_str(), info.time.as_str()), ("16J9EMS1.109", "08112021", "15:06:26"));
+        assert_eq!(isw.check_firmware("16J9EMS1".to_string()), Ok(None));
+        assert!(isw.check_firmware("16Q2EMS1".to_string()).unwrap().unwrap().contains("does not belong"));
+    }
+
+    #[test]
+    fn firmware_info_rejects_non_ascii() {
+        let backend = Arc::new(IswSimBackend::new());
+        let isw = simulated_isw_on(backend.clone());
+        let address = isw.default_profile().unwrap().fw_version.address;
+        backend.write(address, &[0xa1, 0xa2, 0x41, 0x00]).unwrap();
+        assert_eq!(isw.get_firmware_info().map(|info| info.version),
+                   Err(IswError::Parse(format!("Unreadable EC string at <0x{:02x}>: a1 a2 41", address))));
+        assert!(isw.check_firmware("16J9EMS1".to_string()).unwrap().unwrap().starts_with("Unreadable firmware id"));
+    }
+
+    #[test]
+    fn import_writes_nothing_if_either_curve_is_bad() {
+        let mut isw = simulated_isw();
+        let good = FanCurve { temps: vec![50, 55, 60, 65, 70, 75], speeds: vec![0, 40, 50, 60, 70, 80, 90] };
+        let falling = FanCurve { temps: vec![50, 55, 60, 65, 70, 45], speeds: good.speeds.clone() };
+        let curves = FanCurveFile { section: "16J9EMS1".to_string(), cpu: good.clone(), gpu: falling };
+        assert!(matches!(isw.import_fan_curves("16J9EMS1".to_string(), &curves), Err(IswError::OutOfRange(_))));
+        assert_eq!(isw.get_fan_curve("16J9EMS1".to_string(), FanKind::Cpu).unwrap().temps, vec![0; 6]);
+
+        let curves = FanCurveFile { section: "16J9EMS1".to_string(), cpu: good.clone(), gpu: good.clone() };
+        isw.import_fan_curves("16J9EMS1".to_string(), &curves).unwrap();
+        assert_eq!(isw.get_fan_curve("16J9EMS1".to_string(), FanKind::Gpu), Ok(good));
+    }
+
+    fn backup_of(registers: Vec<BackupRegister>) -> IswBackup {
+        IswBackup {
+            created: "20260101T000000Z".to_string(),
+            address_profile: IswConfig::MSI_ADDRESS_DEFAULT.to_string(),
+            registers,
+        }
+    }
+
+    fn register(name: &str, address: u64, width: IswRegisterWidth, value: u16) -> BackupRegister {
+        BackupRegister { name: name.to_string(), address, width, value }
+    }
+
+    #[test]
+    fn restore_puts_back_a_backup() {
+        let mut isw = simulated_isw();
+        isw.set_cooler_boost(true).unwrap();
+        let backup = isw.backup(IswConfig::MSI_ADDRESS_DEFAULT.to_string()).unwrap();
+        isw.set_cooler_boost(false).unwrap();
+        isw.restore(&backup).unwrap();
+        assert_eq!(isw.get_cooler_boost(), Ok(true));
+    }
+
+    #[test]
+    fn restore_refuses_undeclared_register() {
+        let backend = Arc::new(IswSimBackend::new());
+        let mut isw = simulated_isw_on(backend.clone());
+        let backup = backup_of(vec![
+            register("cooler_boost_address", 0x98, IswRegisterWidth::U8, 0x80),
+            register("evil", 0x10, IswRegisterWidth::U16Le, 0xffff),
+        ]);
+        assert_eq!(isw.restore(&backup), Err(IswError::WriteRefused {
+            reason: "Address <0x10> is not declared in the address profile".to_string(),
+            declined: false,
+        }));
+        assert_eq!(isw.dump().unwrap(), vec![0; 256]);
+    }
+
+    #[test]
+    fn restore_refuses_width_mismatch() {
+        let mut isw = simulated_isw();
+        let backup = backup_of(vec![register("cooler_boost_address", 0x98, IswRegisterWidth::U16Le, 0x80)]);
+        assert!(matches!(isw.restore(&backup), Err(IswError::WriteRefused { declined: false, .. })));
+    }
+
+    #[test]
+    fn restore_allows_values_outside_the_rules() {
+        let mut isw = simulated_isw();
+        let backup = backup_of(vec![register("cooler_boost_address", 0x98, IswRegisterWidth::U8, 0x05)]);
+        isw.restore(&backup).unwrap();
+        assert_eq!(isw.raw_access.read_hw(0x98, IswRegisterWidth::U8), Ok(0x05));
+    }
+
+    #[test]
+    fn restore_writes_undeclared_register_once_forced() {
+        let mut isw = simulated_isw();
+        let backup = backup_of(vec![register("evil", 0x10, IswRegisterWidth::U16Le, 0xffff)]);
+        isw.raw_access.set_force(Some(Arc::new(|_: &str| false)));
+        assert!(matches!(isw.restore(&backup), Err(IswError::WriteRefused { declined: true, .. })));
+        isw.raw_access.set_force(Some(Arc::new(|_: &str| true)));
+        isw.restore(&backup).unwrap();
+        assert_eq!(isw.raw_access.read_hw(0x10, IswRegisterWidth::U16Le), Ok(0xffff));
+    }
+
+    #[test]
+    fn restore_rejects_backup_of_other_profile() {
+        let mut isw = simulated_isw();
+        let mut backup = backup_of(vec![register("cooler_boost_address", 0x98, IswRegisterWidth::U8, 0x80)]);
+        backup.address_profile = "MSI_ADDRESS_OTHER".to_string();
+        assert!(matches!(isw.restore(&backup), Err(IswError::Usage(_))));
+        assert_eq!(isw.raw_access.read_hw(0x98, IswRegisterWidth::U8), Ok(0));
+    }
+}
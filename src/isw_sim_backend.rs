@@ -0,0 +1,208 @@
+use std::sync::Mutex;
+use crate::isw_ec_backend::{IswEcBackend, IswFileBackend};
+use crate::isw_error::IswError;
+
+/// Simulated EC holding a register image in memory
+pub struct IswSimBackend {
+    m_image: Mutex<Vec<u8>>,
+    m_image_file: Option<String>,
+    m_save_on_write: bool,
+}
+
+impl IswSimBackend {
+    /// Create a simulated EC with every register set to zero
+    pub fn new() -> IswSimBackend {
+        IswSimBackend {
+            m_image: Mutex::new(vec![0; IswFileBackend::EC_SIZE]),
+            m_image_file: None,
+            m_save_on_write: false,
+        }
+    }
+
+    /// Create a simulated EC from a dump file; with `save_on_write` every write is stored back
+    pub fn load(image_file: String, save_on_write: bool) -> Result<IswSimBackend, IswError> {
+        let image = IswSimBackend::read_image(image_file.clone())?;
+
+        Ok(IswSimBackend {
+            m_image: Mutex::new(image),
+            m_image_file: Some(image_file),
+            m_save_on_write: save_on_write,
+        })
+    }
+
+    /// Write the current register image back to the file it was loaded from
+    pub fn save(&self) -> Result<(), IswError> {
+        match &self.m_image_file {
+            None => Err(IswError::File("Simulated EC has no image file to save to".to_string())),
+            Some(file) => {
+                let image = self.m_image.lock().unwrap().clone();
+                match std::fs::write(file.as_str(), image) {
+                    Ok(_) => Ok(()),
+                    Err(error) => Err(IswError::from_io("Saving image <".to_string() + file.as_str()
+                        + "> failed with <" + error.to_string().as_str() + ">", &error, IswError::File))
+                }
+            }
+        }
+    }
+
+    /// Read a register image from a dump file
+    pub fn read_image(image_file: String) -> Result<Vec<u8>, IswError> {
+        match std::fs::read(image_file.as_str()) {
+            Ok(content) => IswSimBackend::parse_image(&content),
+            Err(error) => Err(IswError::from_io("Opening image <".to_string() + image_file.as_str()
+                + "> failed with <" + error.to_string().as_str() + ">", &error, IswError::File))
+        }
+    }
+
+    /// Accepts either a raw 256-byte image or a 16x16 hex table ("0_ | 00 01 ...")
+    pub fn parse_image(content: &[u8]) -> Result<Vec<u8>, IswError> {
+        if content.len() == IswFileBackend::EC_SIZE {
+            return Ok(content.to_vec());
+        }
+
+        let text = match std::str::from_utf8(content) {
+            Ok(text) => text,
+            Err(_) => {
+                return Err(IswError::Parse("Image is neither a raw dump nor a hex table".to_string()));
+            }
+        };
+
+        let mut image = vec![0; IswFileBackend::EC_SIZE];
+        let mut rows = 0;
+        for line in text.lines() {
+            let mut parts = line.splitn(2, '|');
+            let (label, values) = match (parts.next(), parts.next()) {
+                (Some(label), Some(values)) => (label.trim(), values),
+                _ => continue,
+            };
+            let label = label.trim_start_matches("0x").trim_end_matches('_');
+            let row = match usize::from_str_radix(label, 16) {
+                Ok(row) => row,
+                Err(_) => continue,
+            };
+            let row = if row >= 0x10 { row / 0x10 } else { row };
+
+            let bytes: Vec<&str> = values.split_whitespace().collect();
+            if bytes.len() != 16 || row >= 16 {
+                return Err(IswError::Parse("Malformed hex table row <".to_string() + line + ">"));
+            }
+            for (column, byte) in bytes.iter().enumerate() {
+                match u8::from_str_radix(byte, 16) {
+                    Ok(value) => image[row * 16 + column] = value,
+                    Err(_) => {
+                        return Err(IswError::Parse("Malformed hex table row <".to_string() + line + ">"));
+                    }
+                }
+            }
+            rows += 1;
+        }
+
+        if rows != 16 {
+            return Err(IswError::Parse("Hex table must have 16 rows, found ".to_string() + rows.to_string().as_str()));
+        }
+        Ok(image)
+    }
+
+    fn check_range(base_address: u64, len: usize) -> Result<usize, IswError> {
+        if base_address.saturating_add(len as u64) > IswFileBackend::EC_SIZE as u64 {
+            return Err(IswError::OutOfRange("Access of <".to_string() + len.to_string().as_str() + "> bytes at <"
+                + base_address.to_string().as_str() + "> is outside of the simulated EC"));
+        }
+        Ok(base_address as usize)
+    }
+}
+
+impl Default for IswSimBackend {
+    fn default() -> IswSimBackend {
+        IswSimBackend::new()
+    }
+}
+
+impl IswEcBackend for IswSimBackend {
+    fn read(&self, base_address: u64, buf: &mut [u8]) -> Result<(), IswError> {
+        let start = IswSimBackend::check_range(base_address, buf.len())?;
+        let image = self.m_image.lock().unwrap();
+        buf.copy_from_slice(&image[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write(&self, base_address: u64, data: &[u8]) -> Result<(), IswError> {
+        let start = IswSimBackend::check_range(base_address, data.len())?;
+        self.m_image.lock().unwrap()[start..start + data.len()].copy_from_slice(data);
+        if self.m_save_on_write {
+            self.save()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isw_dump::format_hex_table;
+
+    fn counting_image() -> Vec<u8> {
+        (0..IswFileBackend::EC_SIZE).map(|address| address as u8).collect()
+    }
+
+    #[test]
+    fn parse_image_takes_raw_dump() {
+        let image = counting_image();
+        assert_eq!(IswSimBackend::parse_image(&image), Ok(image));
+    }
+
+    #[test]
+    fn parse_image_takes_hex_table() {
+        let image = counting_image();
+        let table = format_hex_table(&image);
+        assert_eq!(IswSimBackend::parse_image(table.as_bytes()), Ok(image));
+    }
+
+    #[test]
+    fn parse_image_rejects_malformed_row() {
+        let table = format_hex_table(&counting_image()).replace(" 3f\n", " zz\n");
+        match IswSimBackend::parse_image(table.as_bytes()) {
+            Err(IswError::Parse(message)) => assert!(message.starts_with("Malformed hex table row <  3_ |")),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        let short_row = "  0_ | 00 01 02\n";
+        assert!(matches!(IswSimBackend::parse_image(short_row.as_bytes()), Err(IswError::Parse(_))));
+    }
+
+    #[test]
+    fn parse_image_rejects_missing_rows() {
+        let table: String = format_hex_table(&counting_image()).lines().take(10).map(|line| line.to_string() + "\n").collect();
+        assert_eq!(IswSimBackend::parse_image(table.as_bytes()),
+                   Err(IswError::Parse("Hex table must have 16 rows, found 8".to_string())));
+    }
+
+    #[test]
+    fn parse_image_rejects_binary_of_wrong_size() {
+        assert!(matches!(IswSimBackend::parse_image(&[0xff; 17]), Err(IswError::Parse(_))));
+    }
+
+    #[test]
+    fn check_range_accepts_access_up_to_the_last_register() {
+        assert_eq!(IswSimBackend::check_range(0, 1), Ok(0));
+        assert_eq!(IswSimBackend::check_range(0xfe, 2), Ok(0xfe));
+        assert_eq!(IswSimBackend::check_range(0xff, 1), Ok(0xff));
+    }
+
+    #[test]
+    fn check_range_rejects_access_past_the_end() {
+        assert!(matches!(IswSimBackend::check_range(0xff, 2), Err(IswError::OutOfRange(_))));
+        assert!(matches!(IswSimBackend::check_range(0x100, 1), Err(IswError::OutOfRange(_))));
+        assert!(matches!(IswSimBackend::check_range(u64::MAX, 1), Err(IswError::OutOfRange(_))));
+    }
+
+    #[test]
+    fn write_is_read_back() {
+        let backend = IswSimBackend::new();
+        backend.write(0x98, &[0x12, 0x34]).unwrap();
+        let mut buf = [0; 3];
+        backend.read(0x97, &mut buf).unwrap();
+        assert_eq!(buf, [0x00, 0x12, 0x34]);
+        assert!(backend.write(0xff, &[1, 2]).is_err());
+    }
+}
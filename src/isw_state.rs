@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::isw_error::IswError;
+
+const STATE_DIR: &str = "isw-rs";
+
+/// $XDG_STATE_HOME/isw-rs, falling back to ~/.local/state if XDG_STATE_HOME is unset
+pub fn state_dir() -> Result<PathBuf, IswError> {
+    let state_home = match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => Path::new(dir.as_str()).to_path_buf(),
+        _ => match std::env::var("HOME") {
+            Ok(home) if !home.is_empty() => Path::new(home.as_str()).join(".local").join("state"),
+            _ => return Err(IswError::File("Could not locate the state directory; neither XDG_STATE_HOME nor HOME is set".to_string())),
+        }
+    };
+    Ok(state_home.join(STATE_DIR))
+}
+
+/// Current UTC time as "YYYYMMDDTHHMMSSZ"
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let (days, rest) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // civil date from days since 1970-01-01, Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
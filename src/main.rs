@@ -1,13 +1,40 @@
-use crate::isw_rs_base::{IswRsBase, UsbBacklightKind};
+use crate::isw_rs_base::{FanMode, IswReading, IswRsBase, IswSensor, IswValue, UsbBacklightKind};
+use crate::isw_config_model::{FanCurve, FanKind, IswConfig};
 use serde::{Deserialize, Serialize};
 
 mod isw_rs_base;
 mod isw_raw_access;
 mod isw_config_ops;
+mod isw_config_model;
+mod isw_ec_backend;
+mod isw_sim_backend;
+mod isw_dump;
+mod isw_board;
+mod isw_config_check;
+mod isw_curve_file;
+mod isw_backup;
+mod isw_state;
+mod isw_journal;
+mod isw_error;
+mod isw_exit;
+mod isw_output;
+mod isw_exporter;
 mod online;
 
+use std::sync::Arc;
 use clap::{AppSettings, Clap};
 use crate::online::Online;
+use crate::isw_ec_backend::create_backend;
+use crate::isw_raw_access::{IswPendingWrite, IswRegisterWidth};
+use crate::isw_sim_backend::IswSimBackend;
+use crate::isw_config_check::check_config;
+use crate::isw_config_ops::IswConfigOps;
+use crate::isw_curve_file::{CurveFormat, FanCurveFile};
+use crate::isw_backup::IswBackup;
+use crate::isw_journal::IswJournal;
+use crate::isw_error::IswError;
+use crate::isw_exit::*;
+use crate::isw_output::{format_readings, OutputFormat};
 
 #[derive(Serialize, Deserialize, Clone)]
 struct ReceivedOption {
@@ -24,10 +51,35 @@ struct Response {
 #[derive(Clap, Clone)]
 #[clap(version = "0.1", author = "Tobias Egger")]
 #[clap(setting = AppSettings::ArgRequiredElseHelp)]
+#[clap(after_help = EXIT_CODES_HELP)]
 struct Opts {
-    /// Use custom isw-config file
-    #[clap(short, long, default_value = "/home/tobi/CLionProjects/isw-rs/isw.conf")]
-    config: String,
+    /// Use custom isw-config file; its keys override the built-in, /etc/isw-rs and $XDG_CONFIG_HOME/isw-rs configs
+    #[clap(short, long)]
+    config: Option<String>,
+    /// EC access path; 'debugfs' for ec_sys, 'acpi-ec' for the acpi_ec module, 'sim' for a simulated EC
+    #[clap(short, long, default_value = "debugfs")]
+    backend: String,
+    /// Use custom EC node instead of the backend's default; register image (raw or hex table) for 'sim'
+    #[clap(long)]
+    ec_node: Option<String>,
+    /// Store writes to the simulated EC back to its image file
+    #[clap(long)]
+    save_image: bool,
+    /// Root of sysfs, used to read the DMI board name
+    #[clap(long, default_value = "/sys")]
+    sysfs_root: String,
+    /// Back up all writable registers of MSI_ADDRESS_DEFAULT to a state file before a command that writes to the Controller; ignored by restore, undo and --dry-run
+    #[clap(long)]
+    backup: bool,
+    /// Allow writes to registers or values not declared in the address profile, after confirmation
+    #[clap(long)]
+    force: bool,
+    /// Print the writes a command would make instead of writing to the Controller
+    #[clap(long)]
+    dry_run: bool,
+    /// Format of values read by cpu, gpu, get, read and status; 'plain', 'json', 'yaml' or 'csv'
+    #[clap(long, default_value = "plain")]
+    output: String,
     /// Raw Access(Manually Reading and Writing values from/to the Controller)
     #[clap(subcommand)]
     raw: Raw,
@@ -50,10 +102,110 @@ enum Raw {
     Get(StateGetter),
     /// Read CPU-Data
     #[clap(version = "1.3", author = "Tobias Egger")]
-    CPU(CPUHandler),
+    Cpu(CPUHandler),
     /// Read GPU-Data
     #[clap(version = "1.3", author = "Tobias Egger")]
-    GPU(GPUHandler),
+    Gpu(GPUHandler),
+    /// Dump the whole register space of the Controller
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Dump(DumpHandler),
+    /// Show registers that differ between two dumps or a dump and the Controller
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Diff(DiffHandler),
+    /// Highlight registers of the Controller as they change
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Watch(WatchHandler),
+    /// Sample temperatures, fan speeds and fan rpm of CPU and GPU continuously
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Monitor(MonitorHandler),
+    /// Serve EC values as Prometheus metrics over HTTP
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Exporter(ExporterHandler),
+    /// Fan curve Functions
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Curve(CurveHandler),
+    /// Print the config section matching this laptop's board
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Detect,
+    /// Print firmware version and build date of the Controller
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Info,
+    /// Print every sensor and setting, read in a single pass over the Controller
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Status,
+    /// Config file Functions
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Config(ConfigHandler),
+    /// Store all writable registers of the Controller to a timestamped state file
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Backup(BackupHandler),
+    /// Write all registers of a state file back to the Controller
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Restore(RestoreHandler),
+    /// Take back the last writes to the Controller
+    #[clap(version = "1.3", author = "Tobias Egger")]
+    Undo(UndoHandler),
+}
+
+impl Raw {
+    /// Whether the command writes to the Controller; restore and undo are left out on purpose
+    fn writes_ec(&self) -> bool {
+        match self {
+            Raw::Common(_) | Raw::Write(_) => true,
+            Raw::Curve(curve) => curve.apply.is_some() || curve.import.is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// Subcommand for undoing writes
+#[derive(Clap, Clone)]
+struct UndoHandler {
+    /// Number of writes to take back
+    #[clap(short, long, default_value = "1")]
+    steps: usize,
+}
+
+/// Subcommand for backing up the Controller
+#[derive(Clap, Clone)]
+struct BackupHandler {
+    /// Address profile, or board section, naming the registers to back up
+    #[clap(short, long, default_value = "MSI_ADDRESS_DEFAULT")]
+    profile: String,
+    /// State file to write; defaults to a timestamped file in $XDG_STATE_HOME/isw-rs
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+/// Subcommand for restoring the Controller
+#[derive(Clap, Clone)]
+struct RestoreHandler {
+    /// State file to restore; defaults to the latest one in $XDG_STATE_HOME/isw-rs
+    file: Option<String>,
+}
+
+/// Subcommand for the config file
+#[derive(Clap, Clone)]
+#[clap(setting = AppSettings::ArgRequiredElseHelp)]
+struct ConfigHandler {
+    #[clap(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Clap, Clone)]
+enum ConfigCommand {
+    /// Lint the config file; exits non-zero if problems are found
+    Check,
+    /// Print the effective config
+    Show(ConfigShowHandler),
+}
+
+/// Subcommand for printing the config
+#[derive(Clap, Clone)]
+struct ConfigShowHandler {
+    /// Name the file each value comes from
+    #[clap(long)]
+    origin: bool,
 }
 
 /// Subcommand for Writing to Controller
@@ -72,6 +224,9 @@ struct CommonHandler {
     /// Sets Battery-Charging threshold; Accepts any value between 20 and 100
     #[clap(long)]
     battery: Option<u8>,
+    /// Sets Fan mode; 'advanced', 'basic' or 'auto'
+    #[clap(short, long)]
+    fan_mode: Option<String>,
 }
 
 /// Subcommand for Writing to Controller
@@ -84,6 +239,9 @@ struct WriteHandler {
     /// Value to be written
     #[clap(long)]
     value: u16,
+    /// Register width; 'u8', 'u16le' or 'u16be'
+    #[clap(short, long, default_value = "u8")]
+    width: String,
 }
 
 /// Subcommand for Reading from Controller
@@ -93,6 +251,109 @@ struct ReadHandler {
     /// Address to read from
     #[clap(short)]
     address: u64,
+    /// Register width; 'u8', 'u16le' or 'u16be'
+    #[clap(short, long, default_value = "u8")]
+    width: String,
+}
+
+/// Subcommand for Dumping the Controller
+#[derive(Clap, Clone)]
+struct DumpHandler {
+    /// Label every register named in the address profile
+    #[clap(short, long)]
+    annotate: bool,
+    /// Address profile, or board section, used for labels
+    #[clap(short, long, default_value = "MSI_ADDRESS_DEFAULT")]
+    profile: String,
+    /// Additionally store the raw 256-byte image to this file
+    #[clap(short, long)]
+    save: Option<String>,
+}
+
+/// Subcommand for Fan curves
+#[derive(Clap, Clone)]
+#[clap(setting = AppSettings::ArgRequiredElseHelp)]
+struct CurveHandler {
+    /// Writes the CPU- and GPU-fan curve of the given config section (e.g. 16J9EMS1); 'auto' detects it
+    #[clap(long)]
+    apply: Option<String>,
+    /// Shows the fan curves stored in the Controller next to the ones of the given config section; 'auto' detects it
+    #[clap(long)]
+    show: Option<String>,
+    /// Stores the fan curves currently in the Controller to the given config section; 'auto' detects it
+    #[clap(long)]
+    save: Option<String>,
+    /// Exports the fan curves of the given config section as JSON or TOML; 'auto' detects it
+    #[clap(long)]
+    export: Option<String>,
+    /// Export the fan curves currently in the Controller instead of the ones of the config section
+    #[clap(long)]
+    from_ec: bool,
+    /// Writes the fan curves of a JSON or TOML file to the Controller
+    #[clap(long)]
+    import: Option<String>,
+    /// Config section whose address profile --import writes through; defaults to the one named in the file
+    #[clap(long)]
+    section: Option<String>,
+    /// Format of --export and --import; 'json' or 'toml', guessed from the file name if omitted
+    #[clap(long)]
+    format: Option<String>,
+    /// Write --export to this file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+/// Subcommand for Diffing dumps
+#[derive(Clap, Clone)]
+struct DiffHandler {
+    /// Old dump (raw or hex table); reads the Controller if omitted
+    #[clap(long)]
+    old: Option<String>,
+    /// New dump (raw or hex table); reads the Controller if omitted
+    #[clap(long)]
+    new: Option<String>,
+    /// Label changed registers with their names from the address profile
+    #[clap(short, long)]
+    annotate: bool,
+    /// Address profile, or board section, used for labels
+    #[clap(short, long, default_value = "MSI_ADDRESS_DEFAULT")]
+    profile: String,
+}
+
+/// Subcommand for Watching the Controller
+#[derive(Clap, Clone)]
+struct WatchHandler {
+    /// Sampling interval in milliseconds
+    #[clap(short, long, default_value = "500")]
+    interval: u64,
+    /// Print one line per change instead of redrawing the table
+    #[clap(short, long)]
+    log: bool,
+    /// Label changed registers with their names from the address profile
+    #[clap(short, long)]
+    annotate: bool,
+    /// Address profile, or board section, used for labels
+    #[clap(short, long, default_value = "MSI_ADDRESS_DEFAULT")]
+    profile: String,
+}
+
+/// Subcommand for monitoring sensors
+#[derive(Clap, Clone)]
+struct MonitorHandler {
+    /// Time between samples, e.g. '1s', '500ms' or '2m'
+    #[clap(short, long, default_value = "1s")]
+    interval: String,
+    /// Print one JSON object per sample instead of redrawing the table
+    #[clap(long)]
+    jsonl: bool,
+}
+
+/// Subcommand for the Prometheus exporter
+#[derive(Clap, Clone)]
+struct ExporterHandler {
+    /// Address and port to serve /metrics on
+    #[clap(short, long, default_value = "127.0.0.1:9745")]
+    listen: String,
 }
 
 /// Subcommand for getting States from Controller
@@ -108,6 +369,9 @@ struct StateGetter {
     /// Gets Battery-Charging threshold
     #[clap(long)]
     battery: bool,
+    /// Gets Fan mode
+    #[clap(short, long)]
+    fan_mode: bool,
 }
 
 /// Subcommand for Reading CPU-Data
@@ -141,375 +405,586 @@ struct GPUHandler {
 }
 
 fn run_boost(boost: String, isw: &mut IswRsBase) {
-    let status: bool;
-    match boost.as_ref() {
-        "off" => status = false,
-        "on" => status = true,
+    let status = match boost.as_ref() {
+        "off" => false,
+        "on" => true,
         _ => {
-            panic!("Unrecognized option {}", boost);
-        }
-    }
-    match isw.set_cooler_boost(status) {
-        Ok(_) => {}
-        Err(error) => {
-            panic!("{}", error)
+            fail(IswError::Usage(format!("Unrecognized option {}", boost)));
         }
+    };
+    if let Err(error) = isw.set_cooler_boost(status) {
+        fail(error)
     }
 }
 
 fn run_backlight(backlight: String, isw: &mut IswRsBase) {
-    let status: UsbBacklightKind;
-    match backlight.as_ref() {
-        "off" => status = UsbBacklightKind::Off,
-        "half" => status = UsbBacklightKind::Half,
-        "full" => status = UsbBacklightKind::Full,
+    let status = match backlight.as_ref() {
+        "off" => UsbBacklightKind::Off,
+        "half" => UsbBacklightKind::Half,
+        "full" => UsbBacklightKind::Full,
         _ => {
-            panic!("Unrecognized option {}", backlight);
+            fail(IswError::Usage(format!("Unrecognized option {}", backlight)));
         }
+    };
+    if let Err(error) = isw.set_usb_backlight(status) {
+        fail(error)
     }
-    match isw.set_usb_backlight(status) {
-        Ok(_) => {}
-        Err(error) => {
-            panic!("{}", error)
+}
+
+fn run_fan_mode(fan_mode: String, isw: &mut IswRsBase) {
+    let mode = match fan_mode.as_ref() {
+        "advanced" => FanMode::Advanced,
+        "basic" => FanMode::Basic,
+        "auto" => FanMode::Auto,
+        _ => {
+            fail(IswError::Usage(format!("Unrecognized option {}", fan_mode)));
         }
+    };
+    if let Err(error) = isw.set_fan_mode(mode) {
+        fail(error)
     }
 }
 
 fn run_battery(battery: u8, isw: &mut IswRsBase) {
-    match isw.set_battery_threshold(battery) {
-        Ok(_) => {}
-        Err(error) => {
-            panic!("{}", error)
-        }
+    if let Err(error) = isw.set_battery_threshold(battery) {
+        fail(error)
+    }
+}
+
+fn send_response(sock: &mut Online, id: &str, value: String) {
+    let json = Response {
+        id: id.to_string(),
+        value
+    };
+    if let Ok(to_be_sent) = serde_json::to_string(&json) {
+        let trimmed = to_be_sent.trim().to_string();
+        sock.send(trimmed);
     }
 }
 
 fn run_socket(enable: bool, isw: &mut IswRsBase) {
     if enable {
-        let mut sock = Online::new("127.0.0.1".to_string(), 6800, 6799).expect("Cannot open Socket");
+        let mut sock = match Online::new("127.0.0.1".to_string(), 6800, 6799) {
+            Ok(sock) => sock,
+            Err(error) => fail(IswError::from_io(format!("Cannot open Socket: {}", error), &error, IswError::File)),
+        };
         loop {
-            match sock.receive() {
-                Ok(value) => {
-                    if !value.is_empty() {
-                        match serde_json::from_str::<ReceivedOption>(value.as_str()) {
-                            Ok(received) => {
-                                if received.cmd == "cpu_temp"{
-                                    match isw.get_cpu_temp() {
-                                        Ok(value) => {
-                                            let json = Response {
-                                                id: "cpu_temp".to_string(),
-                                                value: value.to_string()
-                                            };
-                                            match serde_json::to_string(&json) {
-                                                Ok(to_be_sent) => {
-                                                    let trimmed = to_be_sent.trim().to_string();
-                                                    sock.send(trimmed);
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                if received.cmd == "gpu_temp"{
-                                    match isw.get_gpu_temp() {
-                                        Ok(value) => {
-                                            let json = Response {
-                                                id: "gpu_temp".to_string(),
-                                                value: value.to_string()
-                                            };
-                                            match serde_json::to_string(&json) {
-                                                Ok(to_be_sent) => {
-                                                    let trimmed = to_be_sent.trim().to_string();
-                                                    sock.send(trimmed);
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                if received.cmd == "cpu_fan_speed"{
-                                    match isw.get_gpu_fan_speed() {
-                                        Ok(value) => {
-                                            let json = Response {
-                                                id: "cpu_fan_speed".to_string(),
-                                                value: value.to_string()
-                                            };
-                                            match serde_json::to_string(&json) {
-                                                Ok(to_be_sent) => {
-                                                    let trimmed = to_be_sent.trim().to_string();
-                                                    sock.send(trimmed);
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                if received.cmd == "gpu_fan_speed"{
-                                    match isw.get_cpu_fan_speed() {
-                                        Ok(value) => {
-                                            let json = Response {
-                                                id: "gpu_fan_speed".to_string(),
-                                                value: value.to_string()
-                                            };
-                                            match serde_json::to_string(&json) {
-                                                Ok(to_be_sent) => {
-                                                    let trimmed = to_be_sent.trim().to_string();
-                                                    sock.send(trimmed);
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                if received.cmd == "cpu_fan_rpm"{
-                                    match isw.get_cpu_fan_rpm() {
-                                        Ok(value) => {
-                                            let json = Response {
-                                                id: "cpu_fan_rpm".to_string(),
-                                                value: value.to_string()
-                                            };
-                                            match serde_json::to_string(&json) {
-                                                Ok(to_be_sent) => {
-                                                    let trimmed = to_be_sent.trim().to_string();
-                                                    sock.send(trimmed);
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                                if received.cmd == "gpu_fan_rpm"{
-                                    match isw.get_gpu_fan_rpm() {
-                                        Ok(value) => {
-                                            let json = Response {
-                                                id: "gpu_fan_rpm".to_string(),
-                                                value: value.to_string()
-                                            };
-                                            match serde_json::to_string(&json) {
-                                                Ok(to_be_sent) => {
-                                                    let trimmed = to_be_sent.trim().to_string();
-                                                    sock.send(trimmed);
-                                                }
-                                                Err(_) => {}
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
+            if let Ok(value) = sock.receive() {
+                if !value.is_empty() {
+                    if let Ok(received) = serde_json::from_str::<ReceivedOption>(value.as_str()) {
+                        if received.cmd == "cpu_temp" {
+                            if let Ok(value) = isw.get_cpu_temp() {
+                                send_response(&mut sock, "cpu_temp", value.to_string());
+                            }
+                        }
+                        if received.cmd == "gpu_temp" {
+                            if let Ok(value) = isw.get_gpu_temp() {
+                                send_response(&mut sock, "gpu_temp", value.to_string());
+                            }
+                        }
+                        if received.cmd == "cpu_fan_speed" {
+                            if let Ok(value) = isw.get_gpu_fan_speed() {
+                                send_response(&mut sock, "cpu_fan_speed", value.to_string());
                             }
-                            Err(_) => {}
-                        };
-                    } else {
-                       println!("Receiving data via Websocket failed");
+                        }
+                        if received.cmd == "gpu_fan_speed" {
+                            if let Ok(value) = isw.get_cpu_fan_speed() {
+                                send_response(&mut sock, "gpu_fan_speed", value.to_string());
+                            }
+                        }
+                        if received.cmd == "cpu_fan_rpm" {
+                            if let Ok(value) = isw.get_cpu_fan_rpm() {
+                                send_response(&mut sock, "cpu_fan_rpm", value.to_string());
+                            }
+                        }
+                        if received.cmd == "gpu_fan_rpm" {
+                            if let Ok(value) = isw.get_gpu_fan_rpm() {
+                                send_response(&mut sock, "gpu_fan_rpm", value.to_string());
+                            }
+                        }
                     }
+                } else {
+                    println!("Receiving data via Websocket failed");
                 }
-                Err(_) => {}
             }
         }
     }
 }
 
-fn run_read(address: u64, isw: &mut IswRsBase) {
-    match isw.raw_access.read_hw(address) {
+fn parse_width(width: String) -> IswRegisterWidth {
+    match IswRegisterWidth::parse(width.as_str()) {
+        Ok(width) => width,
+        Err(error) => {
+            fail(error)
+        }
+    }
+}
+
+fn run_read(address: u64, width: String, output: OutputFormat, isw: &mut IswRsBase) {
+    match isw.raw_access.read_hw(address, parse_width(width)) {
+        Ok(val) if output == OutputFormat::Plain => {
+            println!("Value: {}", val);
+        }
         Ok(val) => {
-            panic!("Value: {}", val);
+            let reading = IswReading {
+                name: format!("0x{:02x}", address),
+                value: IswValue::Number(val as u32),
+                unit: String::new(),
+                raw: val,
+            };
+            print_readings(&[reading], output);
         }
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
     }
 }
 
-fn run_write(address: u64, value: u16, isw: &mut IswRsBase) {
-    match isw.raw_access.write_hw(address, value) {
-        Ok(_) => {}
+fn run_write(address: u64, value: u16, width: String, isw: &mut IswRsBase) {
+    if let Err(error) = isw.raw_access.write_hw(address, parse_width(width), value) {
+        fail(error)
+    }
+}
+
+fn run_dump(dump: DumpHandler, isw: &mut IswRsBase) {
+    let image = match isw.dump() {
+        Ok(image) => image,
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
+        }
+    };
+    print!("{}", isw_dump::format_hex_table(&image));
+
+    if dump.annotate {
+        match isw.get_named_addresses(dump.profile) {
+            Ok(named) => {
+                println!();
+                print!("{}", isw_dump::format_annotations(&image, &named));
+            }
+            Err(error) => {
+                fail(error)
+            }
+        }
+    }
+    if let Some(file) = dump.save {
+        if let Err(error) = std::fs::write(file.as_str(), image) {
+            fail(IswError::from_io("Writing file <".to_string() + file.as_str() + "> failed with <"
+                + error.to_string().as_str() + ">", &error, IswError::File))
         }
     }
 }
 
-fn run_get_battery(isw: &mut IswRsBase) {
-    match isw.get_battery_threshold() {
-        Ok(val) => {
-            println!("Battery threshold: {}", val);
-        }
+fn read_image(file: Option<String>, isw: &mut IswRsBase) -> Vec<u8> {
+    let image = match file {
+        None => isw.dump(),
+        Some(file) => IswSimBackend::read_image(file),
+    };
+    match image {
+        Ok(image) => image,
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
     }
 }
 
-fn run_get_backlight(isw: &mut IswRsBase) {
-    match isw.get_usb_backlight() {
-        Ok(val) => {
-            match val {
-                UsbBacklightKind::Off => {
-                    println!("USB-Backlight is off")
-                }
-                UsbBacklightKind::Half => {
-                    println!("USB-Backlight is at half-strength")
-                }
-                UsbBacklightKind::Full => {
-                    println!("USB-Backlight is at full-strength")
-                }
-                UsbBacklightKind::None => {
-                    println!("No USB-Backlight detected")
-                }
-            }
-        }
+fn get_labels(annotate: bool, profile: String, isw: &mut IswRsBase) -> Vec<(u64, String)> {
+    if !annotate {
+        return Vec::new();
+    }
+    match isw.get_named_addresses(profile) {
+        Ok(named) => named,
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
     }
 }
 
-fn run_get_boost(isw: &mut IswRsBase) {
-    match isw.get_cooler_boost().unwrap() {
-        true => {
-            println!("Coolerboost is on")
-        }
-        false => {
-            println!("Coolerboost is off")
+fn run_diff(diff: DiffHandler, isw: &mut IswRsBase) {
+    let named = get_labels(diff.annotate, diff.profile, isw);
+    let old = read_image(diff.old, isw);
+    let new = read_image(diff.new, isw);
+    print!("{}", isw_dump::format_diff(&isw_dump::diff_images(&old, &new), &named));
+}
+
+fn run_watch(watch: WatchHandler, isw: &mut IswRsBase) {
+    let named = get_labels(watch.annotate, watch.profile, isw);
+    let first = read_image(None, isw);
+    let mut last = first.clone();
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(watch.interval));
+        let current = read_image(None, isw);
+        let changes = isw_dump::diff_images(&last, &current);
+
+        if watch.log {
+            print!("{}", isw_dump::format_diff(&changes, &named));
+        } else {
+            let changed: Vec<u64> = isw_dump::diff_images(&first, &current).iter()
+                .map(|(address, _, _)| *address)
+                .collect();
+            // Clear the screen and move the cursor home before redrawing
+            print!("\x1b[2J\x1b[H");
+            print!("{}", isw_dump::format_highlighted_hex_table(&current, &changed));
+            println!();
+            print!("{}", isw_dump::format_diff(&changes, &named));
         }
+        last = current;
     }
 }
 
-fn run_getters(getter: StateGetter, isw: &mut IswRsBase) {
-    if getter.battery {
-        run_get_battery(isw);
+/// `interval` like "500ms", "2s", "1m" or a bare number of seconds
+fn parse_interval(interval: &str) -> Result<std::time::Duration, IswError> {
+    let (number, unit) = match interval.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => interval.split_at(index),
+        None => (interval, "s"),
+    };
+    let millis: u64 = match unit {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        _ => 0,
+    };
+    let parsed = number.parse::<u64>().ok().filter(|number| millis != 0 && *number != 0);
+    match parsed.and_then(|number| number.checked_mul(millis)) {
+        Some(millis) => Ok(std::time::Duration::from_millis(millis)),
+        None if parsed.is_some() => Err(IswError::Usage("Interval <".to_string() + interval + "> is too long")),
+        None => Err(IswError::Usage("Unrecognized interval <".to_string() + interval
+            + ">; expected a positive number followed by 'ms', 's' or 'm'")),
     }
-    if getter.usb_backlight {
-        run_get_backlight(isw);
+}
+
+fn format_monitor_table(readings: &[IswReading]) -> String {
+    let value = |sensor: IswSensor| match readings.iter().find(|reading| reading.name == sensor.name()) {
+        Some(reading) => reading.value.to_string(),
+        None => "--".to_string(),
+    };
+    let mut table = format!("{:<6}{:>10}{:>10}{:>10}\n", "", "temp °C", "speed %", "rpm");
+    for (name, temp, speed, rpm) in [
+        ("CPU", IswSensor::CpuTemp, IswSensor::CpuFanSpeed, IswSensor::CpuFanRpm),
+        ("GPU", IswSensor::GpuTemp, IswSensor::GpuFanSpeed, IswSensor::GpuFanRpm),
+    ] {
+        table += format!("{:<6}{:>10}{:>10}{:>10}\n", name, value(temp), value(speed), value(rpm)).as_str();
     }
-    if getter.boost {
-        run_get_boost(isw);
+    table
+}
+
+fn format_monitor_line(readings: &[IswReading]) -> String {
+    let mut sample = serde_json::Map::new();
+    sample.insert("timestamp".to_string(), serde_json::Value::from(isw_state::timestamp()));
+    for reading in readings {
+        sample.insert(reading.name.clone(), serde_json::json!(reading.value));
     }
+    serde_json::Value::Object(sample).to_string()
 }
 
-fn run_get_cpu_rpm(isw: &mut IswRsBase) {
-    match isw.get_cpu_fan_rpm() {
-        Ok(val) => {
-            println!("CPU-Fan rpm: {}", val)
-        }
+fn run_monitor(monitor: MonitorHandler, isw: &mut IswRsBase) {
+    let interval = match parse_interval(monitor.interval.as_str()) {
+        Ok(interval) => interval,
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
+        }
+    };
+    loop {
+        let mut readings = Vec::new();
+        for sensor in IswSensor::REALTIME.iter() {
+            match isw.read_sensor(*sensor) {
+                Ok(reading) => readings.push(reading),
+                Err(error) => {
+                    fail(error)
+                }
+            }
+        }
+        if monitor.jsonl {
+            println!("{}", format_monitor_line(&readings));
+        } else {
+            // Clear the screen and move the cursor home before redrawing
+            print!("\x1b[2J\x1b[H");
+            print!("{}", format_monitor_table(&readings));
         }
+        if let Err(error) = std::io::Write::flush(&mut std::io::stdout()) {
+            fail(IswError::File("Writing to stdout failed with <".to_string() + error.to_string().as_str() + ">"))
+        }
+        std::thread::sleep(interval);
     }
 }
 
-fn run_get_cpu_speed(isw: &mut IswRsBase) {
-    match isw.get_cpu_fan_speed() {
-        Ok(val) => {
-            println!("CPU-Fan speed: {}", val)
-        }
+fn run_exporter(listen: String, isw: &mut IswRsBase) {
+    if let Err(error) = isw_exporter::serve(listen.as_str(), isw) {
+        fail(error)
+    }
+}
+
+fn format_fan_curve_table(name: &str, section: &str, active: &FanCurve, expected: &FanCurve) -> String {
+    let mut table = format!("{:<16}{:>6}{:>10}\n", name, "EC", section);
+    let rows = active.temps.iter().zip(expected.temps.iter())
+        .enumerate()
+        .map(|(i, values)| ("temp_".to_string() + i.to_string().as_str(), values))
+        .chain(active.speeds.iter().zip(expected.speeds.iter())
+            .enumerate()
+            .map(|(i, values)| ("fan_speed_".to_string() + i.to_string().as_str(), values)));
+    for (key, (ec, config)) in rows {
+        let marker = if ec != config { " *" } else { "" };
+        table += format!("{:<16}{:>6}{:>10}{}\n", key, ec, config, marker).as_str();
+    }
+    table
+}
+
+fn run_show_curve(section: String, isw: &mut IswRsBase) {
+    for (kind, name) in [(FanKind::Cpu, "CPU"), (FanKind::Gpu, "GPU")] {
+        let active = match isw.get_fan_curve(section.clone(), kind) {
+            Ok(curve) => curve,
+            Err(error) => {
+                fail(error)
+            }
+        };
+        let expected = match isw.get_config_fan_curve(section.clone(), kind) {
+            Ok(curve) => curve,
+            Err(error) => {
+                fail(error)
+            }
+        };
+        println!("{}", format_fan_curve_table(name, section.as_str(), &active, &expected));
+    }
+}
+
+fn resolve_section(section: String, sysfs_root: String, isw: &mut IswRsBase) -> String {
+    if section != "auto" {
+        return section;
+    }
+    match isw.detect_section(sysfs_root) {
+        Ok(section) => section,
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
     }
 }
 
-fn run_get_cpu_temp(isw: &mut IswRsBase) {
-    match isw.get_cpu_temp() {
-        Ok(val) => {
-            println!("CPU temperature: {}", val)
+fn warn_firmware(section: String, isw: &mut IswRsBase) {
+    match isw.check_firmware(section) {
+        Ok(None) => {}
+        Ok(Some(warning)) => {
+            eprintln!("Warning: {}", warning)
         }
         Err(error) => {
-            panic!("{}", error)
+            eprintln!("Warning: Could not check EC firmware: {}", error)
         }
     }
 }
 
-fn run_get_gpu_rpm(isw: &mut IswRsBase) {
-    match isw.get_gpu_fan_rpm() {
-        Ok(val) => {
-            println!("GPU-Fan rpm: {}", val)
+fn run_import_curve(file: String, section: Option<String>, format: Option<String>, sysfs_root: String,
+                    isw: &mut IswRsBase) {
+    let curves = match CurveFormat::select(format, Some(file.as_str()))
+        .and_then(|format| FanCurveFile::load(file.as_str(), format)) {
+        Ok(curves) => curves,
+        Err(error) => fail(error),
+    };
+    let section = resolve_section(section.unwrap_or_else(|| curves.section.clone()), sysfs_root, isw);
+    warn_firmware(section.clone(), isw);
+    if let Err(error) = isw.import_fan_curves(section, &curves) {
+        fail(error)
+    }
+}
+
+fn run_export_curve(section: String, from_ec: bool, format: Option<String>, output: Option<String>,
+                    sysfs_root: String, isw: &mut IswRsBase) {
+    let section = resolve_section(section, sysfs_root, isw);
+    let exported = CurveFormat::select(format, output.as_deref()).and_then(|format| {
+        let curves = isw.export_fan_curves(section, from_ec)?;
+        match &output {
+            Some(file) => curves.save(file.as_str(), format),
+            None => curves.to_text(format).map(|text| println!("{}", text.trim_end())),
         }
-        Err(error) => {
-            panic!("{}", error)
+    });
+    if let Err(error) = exported {
+        fail(error)
+    }
+}
+
+fn run_curve(curve: CurveHandler, sysfs_root: String, isw: &mut IswRsBase) {
+    if let Some(section) = curve.apply {
+        let section = resolve_section(section, sysfs_root.clone(), isw);
+        warn_firmware(section.clone(), isw);
+        if let Err(error) = isw.apply_fan_curve(section) {
+            fail(error)
+        }
+    }
+    if let Some(file) = curve.import {
+        run_import_curve(file, curve.section, curve.format.clone(), sysfs_root.clone(), isw);
+    }
+    if let Some(section) = curve.show {
+        let section = resolve_section(section, sysfs_root.clone(), isw);
+        run_show_curve(section, isw);
+    }
+    if let Some(section) = curve.save {
+        let section = resolve_section(section, sysfs_root.clone(), isw);
+        if let Err(error) = isw.save_active_fan_curve(section) {
+            fail(error)
         }
     }
+    if let Some(section) = curve.export {
+        run_export_curve(section, curve.from_ec, curve.format, curve.output, sysfs_root, isw);
+    }
 }
 
-fn run_get_gpu_speed(isw: &mut IswRsBase) {
-    match isw.get_gpu_fan_speed() {
-        Ok(val) => {
-            println!("GPU-Fan speed: {}", val)
+fn run_info(isw: &mut IswRsBase) {
+    match isw.get_firmware_info() {
+        Ok(info) => {
+            println!("Firmware version: {}", info.version);
+            println!("Build date: {}", info.date);
+            println!("Build time: {}", info.time);
         }
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
     }
 }
 
-fn run_get_gpu_temp(isw: &mut IswRsBase) {
-    match isw.get_gpu_temp() {
-        Ok(val) => {
-            println!("GPU temperature: {}", val)
+fn run_detect(sysfs_root: String, isw: &mut IswRsBase) {
+    println!("{}", resolve_section("auto".to_string(), sysfs_root, isw));
+}
+
+/// The sentence isw-rs printed for `reading` before --output existed
+fn describe_reading(sensor: IswSensor, reading: &IswReading) -> String {
+    let value = reading.value.to_string();
+    match sensor {
+        IswSensor::BatteryThreshold => "Battery threshold: ".to_string() + value.as_str(),
+        IswSensor::UsbBacklight => match value.as_str() {
+            "off" => "USB-Backlight is off".to_string(),
+            "half" => "USB-Backlight is at half-strength".to_string(),
+            "full" => "USB-Backlight is at full-strength".to_string(),
+            _ => "No USB-Backlight detected".to_string(),
+        },
+        IswSensor::FanMode => match value.as_str() {
+            "unknown" => "Unknown Fan mode".to_string(),
+            mode => "Fan mode is ".to_string() + mode,
+        },
+        IswSensor::CoolerBoost => match reading.value {
+            IswValue::Flag(true) => "Coolerboost is on".to_string(),
+            _ => "Coolerboost is off".to_string(),
+        },
+        IswSensor::CpuFanRpm => "CPU-Fan rpm: ".to_string() + value.as_str(),
+        IswSensor::CpuFanSpeed => "CPU-Fan speed: ".to_string() + value.as_str(),
+        IswSensor::CpuTemp => "CPU temperature: ".to_string() + value.as_str(),
+        IswSensor::GpuFanRpm => "GPU-Fan rpm: ".to_string() + value.as_str(),
+        IswSensor::GpuFanSpeed => "GPU-Fan speed: ".to_string() + value.as_str(),
+        IswSensor::GpuTemp => "GPU temperature: ".to_string() + value.as_str(),
+    }
+}
+
+fn print_readings(readings: &[IswReading], output: OutputFormat) {
+    match format_readings(readings, output) {
+        Ok(text) => print!("{}", text),
+        Err(error) => fail(error),
+    }
+}
+
+fn run_sensors(sensors: Vec<IswSensor>, output: OutputFormat, isw: &mut IswRsBase) {
+    let mut readings = Vec::new();
+    for sensor in sensors {
+        match isw.read_sensor(sensor) {
+            Ok(reading) => {
+                if output == OutputFormat::Plain {
+                    println!("{}", describe_reading(sensor, &reading));
+                }
+                readings.push(reading);
+            }
+            Err(error) => {
+                fail(error)
+            }
         }
+    }
+    if output != OutputFormat::Plain {
+        print_readings(&readings, output);
+    }
+}
+
+fn run_status(output: OutputFormat, isw: &mut IswRsBase) {
+    let snapshot = match isw.snapshot() {
+        Ok(snapshot) => snapshot,
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
+    };
+    if output == OutputFormat::Plain {
+        for sensor in IswSensor::ALL.iter() {
+            if let Some(reading) = snapshot.reading(*sensor) {
+                println!("{}", describe_reading(*sensor, reading));
+            }
+        }
+    } else {
+        print_readings(&snapshot.readings, output);
+    }
+}
+
+fn run_getters(getter: StateGetter, output: OutputFormat, isw: &mut IswRsBase) {
+    let mut sensors = Vec::new();
+    if getter.battery {
+        sensors.push(IswSensor::BatteryThreshold);
     }
+    if getter.usb_backlight {
+        sensors.push(IswSensor::UsbBacklight);
+    }
+    if getter.boost {
+        sensors.push(IswSensor::CoolerBoost);
+    }
+    if getter.fan_mode {
+        sensors.push(IswSensor::FanMode);
+    }
+    run_sensors(sensors, output, isw);
 }
 
-fn run_cpu(cpu: CPUHandler, isw: &mut IswRsBase) {
+fn run_cpu(cpu: CPUHandler, output: OutputFormat, isw: &mut IswRsBase) {
+    let mut sensors = Vec::new();
     if cpu.rpm {
-        run_get_cpu_rpm(isw);
+        sensors.push(IswSensor::CpuFanRpm);
     }
     if cpu.speed {
-        run_get_cpu_speed(isw);
+        sensors.push(IswSensor::CpuFanSpeed);
     }
     if cpu.temperature {
-        run_get_cpu_temp(isw);
+        sensors.push(IswSensor::CpuTemp);
     }
+    run_sensors(sensors, output, isw);
 }
 
 fn run_common(common: CommonHandler, isw: &mut IswRsBase) {
-    match common.boost {
-        None => {}
-        Some(boost) => {
-            run_boost(boost, isw);
-        }
+    if let Some(boost) = common.boost {
+        run_boost(boost, isw);
     }
-    match common.usb_backlight {
-        None => {}
-        Some(backlight) => {
-            run_backlight(backlight, isw);
-        }
+    if let Some(backlight) = common.usb_backlight {
+        run_backlight(backlight, isw);
     }
-    match common.battery {
-        None => {}
-        Some(battery) => {
-            run_battery(battery, isw);
-        }
+    if let Some(battery) = common.battery {
+        run_battery(battery, isw);
     }
-    match common.socket {
-        None => {}
-        Some(enable) => {
-            run_socket(enable, isw);
-        }
+    if let Some(fan_mode) = common.fan_mode {
+        run_fan_mode(fan_mode, isw);
+    }
+    if let Some(enable) = common.socket {
+        run_socket(enable, isw);
     }
 }
 
-fn run_gpu(gpu: GPUHandler, isw: &mut IswRsBase) {
+fn run_gpu(gpu: GPUHandler, output: OutputFormat, isw: &mut IswRsBase) {
+    let mut sensors = Vec::new();
     if gpu.rpm {
-        run_get_gpu_rpm(isw);
+        sensors.push(IswSensor::GpuFanRpm);
     }
     if gpu.speed {
-        run_get_gpu_speed(isw);
+        sensors.push(IswSensor::GpuFanSpeed);
     }
     if gpu.temperature {
-        run_get_gpu_temp(isw);
+        sensors.push(IswSensor::GpuTemp);
     }
+    run_sensors(sensors, output, isw);
 }
 
 fn run(isw: &mut IswRsBase, opts: Opts) {
+    let output = match OutputFormat::parse(opts.output.as_str()) {
+        Ok(output) => output,
+        Err(error) => {
+            fail(error)
+        }
+    };
     /*
     match opts.boost {
         None => {}
@@ -531,38 +1006,263 @@ fn run(isw: &mut IswRsBase, opts: Opts) {
     }*/
     match opts.raw {
         Raw::Write(write) => {
-            run_write(write.address, write.value, isw);
+            run_write(write.address, write.value, write.width, isw);
         }
         Raw::Read(read) => {
-            run_read(read.address, isw);
+            run_read(read.address, read.width, output, isw);
         }
         Raw::Get(getter) => {
-            run_getters(getter, isw);
+            run_getters(getter, output, isw);
         }
-        Raw::CPU(cpu) => {
-            run_cpu(cpu, isw);
+        Raw::Cpu(cpu) => {
+            run_cpu(cpu, output, isw);
         }
-        Raw::GPU(gpu) => {
-            run_gpu(gpu, isw);
+        Raw::Gpu(gpu) => {
+            run_gpu(gpu, output, isw);
         }
         Raw::Common(common) => {
             run_common(common, isw);
         }
+        Raw::Dump(dump) => {
+            run_dump(dump, isw);
+        }
+        Raw::Diff(diff) => {
+            run_diff(diff, isw);
+        }
+        Raw::Watch(watch) => {
+            run_watch(watch, isw);
+        }
+        Raw::Monitor(monitor) => {
+            run_monitor(monitor, isw);
+        }
+        Raw::Exporter(exporter) => {
+            run_exporter(exporter.listen, isw);
+        }
+        Raw::Curve(curve) => {
+            run_curve(curve, opts.sysfs_root, isw);
+        }
+        Raw::Detect => {
+            run_detect(opts.sysfs_root, isw);
+        }
+        Raw::Info => {
+            run_info(isw);
+        }
+        Raw::Status => {
+            run_status(output, isw);
+        }
+        Raw::Config(_) => {
+            // handled in parse(), before the config is loaded
+        }
+        Raw::Backup(backup) => {
+            run_backup(backup.profile, backup.output, isw);
+        }
+        Raw::Restore(restore) => {
+            run_restore(restore.file, isw);
+        }
+        Raw::Undo(undo) => {
+            run_undo(undo.steps, isw);
+        }
+    }
+}
+
+fn run_undo(steps: usize, isw: &mut IswRsBase) {
+    match isw.raw_access.undo(steps) {
+        Ok(undone) => {
+            for entry in undone {
+                println!("0x{:02x}: {} -> {} ({}, {})", entry.address, entry.new, entry.old,
+                         entry.timestamp, entry.command);
+            }
+        }
+        Err(error) => {
+            fail(error)
+        }
+    }
+}
+
+fn run_backup(profile: String, output: Option<String>, isw: &mut IswRsBase) {
+    let saved = isw.backup(profile).and_then(|backup| {
+        let file = match output {
+            Some(file) => file,
+            None => IswBackup::default_file(backup.created.as_str())?,
+        };
+        backup.save(file.as_str())?;
+        Ok(file)
+    });
+    match saved {
+        Ok(file) => {
+            eprintln!("Backed up EC to {}", file)
+        }
+        Err(error) => {
+            fail(error)
+        }
+    }
+}
+
+fn run_restore(file: Option<String>, isw: &mut IswRsBase) {
+    let restored = file.map_or_else(IswBackup::latest_file, Ok).and_then(|file| {
+        let backup = IswBackup::load(file.as_str())?;
+        isw.restore(&backup)?;
+        Ok(file)
+    });
+    match restored {
+        Ok(file) => {
+            eprintln!("Restored EC from {}", file)
+        }
+        Err(error) => {
+            fail(error)
+        }
+    }
+}
+
+fn run_config_check(cfg_file: Option<String>) {
+    match check_config(cfg_file) {
+        Ok(problems) => {
+            if problems.is_empty() {
+                println!("OK");
+                return;
+            }
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+            println!("{} problem(s)", problems.len());
+            std::process::exit(EXIT_CONFIG);
+        }
+        Err(error) => {
+            fail(error)
+        }
+    }
+}
+
+fn run_config_show(show: ConfigShowHandler, cfg_file: Option<String>) {
+    let mut ops = IswConfigOps::new(cfg_file);
+    if let Err(error) = ops.load_config() {
+        fail(error)
+    }
+    for section in ops.get_sections() {
+        let properties = match ops.get_section(section.clone()) {
+            Ok(properties) => properties,
+            Err(error) => fail(error),
+        };
+        let mut keys: Vec<&String> = properties.keys().collect();
+        if keys.is_empty() {
+            continue;
+        }
+        keys.sort();
+        println!("[{}]", section);
+        for key in keys {
+            if show.origin {
+                let origin = ops.get_origin(section.clone(), key.clone()).unwrap_or_default();
+                println!("{} = {}    # {}", key, properties[key], origin);
+            } else {
+                println!("{} = {}", key, properties[key]);
+            }
+        }
+        println!();
+    }
+}
+
+fn run_config(config: ConfigHandler, cfg_file: Option<String>) {
+    match config.command {
+        ConfigCommand::Check => {
+            run_config_check(cfg_file);
+        }
+        ConfigCommand::Show(show) => {
+            run_config_show(show, cfg_file);
+        }
+    }
+}
+
+/// Ask on the terminal whether a write breaking the allowlist should go ahead
+fn confirm_forced_write(reason: &str) -> bool {
+    eprint!("{}. Write anyway? [y/N] ", reason);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Print a write held back by --dry-run
+fn print_pending_write(write: &IswPendingWrite) {
+    match &write.name {
+        Some(name) => println!("0x{:02x}: {} -> {} ({})", write.address, write.old, write.new, name),
+        None => println!("0x{:02x}: {} -> {}", write.address, write.old, write.new),
     }
 }
 
 fn parse() {
     let opts: Opts = Opts::parse();
-    match IswRsBase::new(opts.clone().config) {
+    if let Raw::Config(config) = opts.raw.clone() {
+        run_config(config, opts.config);
+        return;
+    }
+    let backend = match create_backend(opts.backend.clone(), opts.ec_node.clone(), opts.save_image) {
+        Ok(backend) => backend,
+        Err(error) => {
+            fail(error)
+        }
+    };
+    match IswRsBase::new(opts.clone().config, backend) {
         Ok(mut isw) => {
+            let command = std::env::args().collect::<Vec<String>>().join(" ");
+            match IswJournal::open(command) {
+                Ok(journal) => isw.raw_access.set_journal(Some(journal)),
+                Err(error) => eprintln!("Warning: EC writes are not journaled: {}", error),
+            }
+            if opts.force {
+                isw.raw_access.set_force(Some(Arc::new(confirm_forced_write)));
+            }
+            if opts.dry_run {
+                isw.raw_access.set_dry_run(Some(Arc::new(print_pending_write)));
+            }
+            // a backup taken now would be the one restore picks, and undo only takes back journaled writes;
+            // a dry run writes nothing worth backing up
+            if opts.backup && !opts.dry_run && opts.raw.writes_ec() {
+                run_backup(IswConfig::MSI_ADDRESS_DEFAULT.to_string(), None, &mut isw);
+            }
             run(&mut isw, opts.clone())
         }
         Err(error) => {
-            panic!("{}", error)
+            fail(error)
         }
     }
 }
 
 fn main() {
+    // die quietly like other filters when stdout is closed early, e.g. by `isw-rs config show | head`,
+    // instead of panicking on the failed print
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
     parse();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_interval_reads_units() {
+        assert_eq!(parse_interval("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_interval("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_interval("3"), Ok(Duration::from_secs(3)));
+        assert_eq!(parse_interval("1m"), Ok(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn parse_interval_rejects_zero_and_unknown_units() {
+        for interval in ["0s", "5h", "s", "-1s", ""].iter() {
+            assert!(matches!(parse_interval(interval), Err(IswError::Usage(_))), "{}", interval);
+        }
+    }
+
+    #[test]
+    fn parse_interval_rejects_overflow() {
+        assert_eq!(parse_interval("18446744073709551615m"),
+                   Err(IswError::Usage("Interval <18446744073709551615m> is too long".to_string())));
+        assert!(matches!(parse_interval("99999999999999999999s"), Err(IswError::Usage(_))));
+        assert_eq!(parse_interval("18446744073709551615ms"), Ok(Duration::from_millis(u64::MAX)));
+    }
+}
+
@@ -21,14 +21,7 @@ impl Online {
     }
     pub fn send(&mut self, data: String) -> usize {
         let build = self.ip.clone() + ":" + &*self.port_tx.to_string();
-        match self.socket.send_to(data.as_bytes(), build) {
-            Ok(val) => {
-                val
-            }
-            Err(_) => {
-                0
-            }
-        }
+        self.socket.send_to(data.as_bytes(), build).unwrap_or_default()
     }
 
     pub fn receive(&mut self) -> Result<String, std::io::Error> {
@@ -37,7 +30,7 @@ impl Online {
             Ok(val) => {
                 let addr = val.1;
 
-                println!("own {}, foreign {}", self.socket.local_addr().unwrap().ip().to_string(), addr.ip().to_string());
+                println!("own {}, foreign {}", self.socket.local_addr().unwrap().ip(), addr.ip());
 
                 if addr.ip().to_string() != self.socket.local_addr().unwrap().ip().to_string()
                 {